@@ -0,0 +1,37 @@
+use crate::args::Args;
+use std::io::Write;
+
+/// The archive line for a download: the media id followed by every option that changes the downloaded file
+pub fn entry(args: &Args) -> String {
+    format!(
+        "{} {}",
+        crate::service::media_id(args.c_url.as_ref().unwrap()),
+        [
+            format!("mode={}", format!("{:?}", args.c_download_mode).to_lowercase()),
+            format!("vcodec={}", args.c_video_codec.print()),
+            format!("vquality={}", args.c_video_quality),
+            format!("aformat={}", args.c_audio_format.print()),
+            format!("bitrate={}", args.c_audio_bitrate),
+            format!("dublang={}", args.accept_language),
+            format!("gif={}", args.c_twitter_gif),
+            format!("tt-full-audio={}", args.c_tt_full_audio),
+            format!("tt-h265={}", args.c_tt_h265),
+            format!("no-metadata={}", args.c_disable_metadata)
+        ].join(",")
+    )
+}
+
+pub fn contains(path: &str, entry: &str) -> bool {
+    std::fs::read_to_string(crate::proc::expand_home(path))
+        .map(|text| text.lines().any(|line| line.trim() == entry))
+        .unwrap_or(false)
+}
+
+pub fn record(path: &str, entry: &str) -> std::io::Result<()> {
+    let path = crate::proc::expand_home(path);
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(format!("{entry}\n").as_bytes())
+}
@@ -0,0 +1,196 @@
+use super::types::ParseError;
+
+/// A line from a "bulk execute" file that has something to run on it
+#[derive(Debug, Clone, PartialEq)]
+pub struct BulkLine {
+    /// 1-based, counting blank and comment lines
+    pub number: usize,
+    pub text: String,
+    pub tokens: Vec<String>,
+    /// Flags from the directives above this line, which go before the line's own flags
+    pub defaults: Vec<String>
+}
+impl BulkLine {
+    /// The line with its defaults written out, so it means the same thing without the directives above it
+    pub fn standalone(&self) -> String {
+        if self.defaults.is_empty() {
+            return self.text.clone();
+        }
+        let defaults: Vec<String> = self.defaults.iter().map(|d| quote(d)).collect();
+        format!("{} {}", defaults.join(" "), self.text)
+    }
+}
+
+/// Splits a line into arguments the way a shell would.
+/// Whitespace separates arguments, '' quotes everything literally, "" quotes everything but \" and \\,
+/// a backslash outside of quotes escapes the next character, and a # at the start of an argument comments out the rest of the line.
+/// ${VAR} is replaced with the environment variable VAR, except inside '' quotes. Unlike a shell, the value is never split on spaces.
+/// Errors come with the 1-based column they happened at
+pub fn tokenize(line: &str) -> Result<Vec<String>, (usize, String)> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut chars = line.chars().enumerate().peekable();
+
+    while let Some((col, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            },
+            '#' if !in_token => break,
+            '\'' => {
+                in_token = true;
+                loop {
+                    match chars.next() {
+                        Some((_, '\'')) => break,
+                        Some((_, c)) => current.push(c),
+                        None => return Err((col + 1, String::from("This ' quote is never closed")))
+                    }
+                }
+            },
+            '"' => {
+                in_token = true;
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.peek() {
+                            Some((_, next)) if *next == '"' || *next == '\\' => {
+                                current.push(*next);
+                                chars.next();
+                            },
+                            _ => current.push('\\')
+                        },
+                        Some((var_col, '$')) if chars.peek().is_some_and(|(_, next)| *next == '{') => current.push_str(&variable(&mut chars, var_col)?),
+                        Some((_, c)) => current.push(c),
+                        None => return Err((col + 1, String::from("This \" quote is never closed")))
+                    }
+                }
+            },
+            '\\' => {
+                in_token = true;
+                match chars.next() {
+                    Some((_, c)) => current.push(c),
+                    None => return Err((col + 1, String::from("There is nothing after this \\ to escape")))
+                }
+            },
+            '$' if chars.peek().is_some_and(|(_, next)| *next == '{') => {
+                in_token = true;
+                current.push_str(&variable(&mut chars, col)?);
+            },
+            c => {
+                in_token = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+/// Reads the {VAR} after a $ and looks it up in the environment
+fn variable(chars: &mut std::iter::Peekable<std::iter::Enumerate<std::str::Chars>>, col: usize) -> Result<String, (usize, String)> {
+    chars.next();
+    let mut name = String::new();
+    loop {
+        match chars.next() {
+            Some((_, '}')) => break,
+            Some((_, c)) if c.is_ascii_alphanumeric() || c == '_' => name.push(c),
+            _ => return Err((col + 1, String::from("Variables must look like ${NAME}, with only letters, numbers and _ in the name")))
+        }
+    }
+    std::env::var(&name).map_err(|_| (col + 1, format!("The environment variable {name} is not set")))
+}
+
+/// Tokenizes every line of a "bulk execute" file, leaving out blank lines and comments.
+/// Directive lines starting with @ set default flags for every line after them:
+/// `@default <flags>` adds flags, `@output-dir <dir>` and `@instance <url | idx>` are shortcuts for -d and -i, and `@reset` clears them
+pub fn parse_file(contents: &str) -> Result<Vec<BulkLine>, ParseError> {
+    let mut lines: Vec<BulkLine> = Vec::new();
+    let mut defaults: Vec<String> = Vec::new();
+    for (i, text) in contents.lines().enumerate() {
+        let error = |col: Option<usize>, message: &str| ParseError::throw_bulkerr(&line_error(i + 1, col, message, text));
+        let tokens = match tokenize(text) {
+            Ok(tokens) if tokens.is_empty() => continue,
+            Ok(tokens) => tokens,
+            Err((col, message)) => return Err(error(Some(col), &message))
+        };
+
+        if !tokens[0].starts_with('@') {
+            lines.push(BulkLine {
+                number: i + 1,
+                text: text.trim().to_string(),
+                tokens,
+                defaults: defaults.clone()
+            });
+            continue;
+        }
+        let col = text.find('@').map(|idx| idx + 1);
+        let mut added = match (tokens[0].to_lowercase().as_str(), &tokens[1..]) {
+            ("@reset", []) => {
+                defaults.clear();
+                continue;
+            },
+            ("@default", flags) if !flags.is_empty() => flags.to_vec(),
+            ("@output-dir", [dir]) => vec![String::from("--output-dir"), dir.clone()],
+            ("@instance", [instance]) => vec![String::from("--instance"), instance.clone()],
+            ("@default" | "@output-dir" | "@instance" | "@reset", _) => return Err(error(col, &format!("Wrong number of arguments for {}", tokens[0]))),
+            (directive, _) => return Err(error(col, &format!("Unknown directive {directive} (use @default, @output-dir, @instance or @reset)")))
+        };
+
+        // check the flags now, so the error points at the directive instead of every line after it
+        let mut check = vec!["get", "https://"];
+        check.extend(added.iter().map(|f| f.as_str()));
+        if let Err(e) = super::Args::override_args(&check).parse() {
+            return Err(error(error_column(text, &tokens, &e.print()), &e.print()));
+        }
+        defaults.append(&mut added);
+    }
+    Ok(lines)
+}
+
+/// Reads a plain list of links, one per line, leaving out blank lines and # comments
+pub fn parse_url_list(contents: &str) -> Result<Vec<String>, ParseError> {
+    let mut urls: Vec<String> = Vec::new();
+    for (i, text) in contents.lines().enumerate() {
+        let url = text.split(" #").next().unwrap_or_default().trim();
+        if url.is_empty() || url.starts_with('#') {
+            continue;
+        }
+        if !url.contains("https://") || url.contains(char::is_whitespace) {
+            let col = text.find(url).map(|idx| idx + 1);
+            return Err(ParseError::throw_bulkerr(&line_error(i + 1, col, "Each line of a link list must be a single https:// link", text)));
+        }
+        urls.push(url.to_string());
+    }
+    Ok(urls)
+}
+
+/// Formats an error for a line of a "bulk execute" file, quoting the line and pointing at the column if there is one
+pub fn line_error(number: usize, col: Option<usize>, message: &str, text: &str) -> String {
+    match col {
+        Some(col) => {
+            let pointer = " ".repeat(text.chars().take(col - 1).map(|c| if c == '\t' { 8 } else { 1 }).sum());
+            format!("On line {number}, column {col} | {message}\n    {}\n    {pointer}^", text.replace('\t', "        "))
+        },
+        None => format!("On line {number} | {message}\n    {}", text.trim())
+    }
+}
+
+/// Finds the column of the argument an error is about, for errors that end with the argument like "Unrecognized argument: -z"
+pub fn error_column(text: &str, tokens: &[String], error: &str) -> Option<usize> {
+    let token = tokens.iter().find(|t| !t.is_empty() && error.to_lowercase().ends_with(&format!(": {}", t.to_lowercase())))?;
+    text.find(token.as_str()).map(|idx| text[..idx].chars().count() + 1)
+}
+
+/// Quotes an argument for a "bulk execute" file if it needs it, so that it tokenizes back into the same argument
+pub fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.chars().any(|c| c.is_whitespace() || matches!(c, '\'' | '"' | '\\' | '#' | '$')) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
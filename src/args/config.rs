@@ -1,124 +1,526 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use super::{spec, types, Args};
 
 #[cfg(unix)]
 const CONFIG_PATH: &str = "$CFG/.config/tcobalt.conf";
 #[cfg(target_os = "windows")]
 const CONFIG_PATH: &str = "$CFG\\tcobalt.conf";
 
-pub fn load_config_into(args: &mut Vec<String>, instance_list: &mut Vec<String>) {
-    let path = PathBuf::from({
+/// The environment variable that points tcobalt at another config file
+pub const CONFIG_VAR: &str = "TCOBALT_CONFIG";
+
+pub fn config_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(CONFIG_VAR).filter(|path| !path.is_empty()) {
+        return PathBuf::from(path);
+    }
+    PathBuf::from({
         if cfg!(target_os = "windows") {
             CONFIG_PATH.replace("$CFG", &std::env::var("LOCALAPPDATA").expect("no localappdata var"))
         } else {
             CONFIG_PATH.replace("$CFG", &std::env::var("HOME").expect("no home var"))
         }
-    });
+    })
+}
+
+/// The environment variable that chooses a profile when --profile isn't given
+pub const PROFILE_VAR: &str = "TCOBALT_PROFILE";
+
+/// The profile chosen with the TCOBALT_PROFILE environment variable, if it is set
+pub fn env_profile() -> Option<String> {
+    std::env::var(PROFILE_VAR).ok().filter(|profile| !profile.trim().is_empty())
+}
+
+/// Adds the options from the config file to `args` and the [default.instances] section to `instance_list`.
+/// With a profile, the keys of its [profile.NAME] section replace the same keys from [default]
+/// Fails on values the options don't take in the sections that get loaded, and warns once about every other problem `check` finds
+pub fn load_config_into(args: &mut Vec<String>, instance_list: &mut Vec<String>, profile: Option<&str>) -> Result<(), String> {
+    static WARNED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+    let path = config_path();
+    let text = std::fs::read_to_string(&path).unwrap_or_default();
+    let (blocking, ignored): (Vec<Problem>, Vec<Problem>) = check(&text).into_iter().partition(|problem| problem.blocks(profile));
+    if !blocking.is_empty() {
+        return Err(format!("The config file has {} problem(s), run `tcb config check` after fixing them:\n{}", blocking.len(), diagnostics(&path, &blocking)));
+    }
+    // the config is loaded again for every bulk line, but the warning only needs to be seen once
+    if !ignored.is_empty() && !WARNED.swap(true, std::sync::atomic::Ordering::Relaxed) {
+        eprintln!("Ignoring {} problem(s) in the config file, run `tcb config check` to fix them:\n{}", ignored.len(), diagnostics(&path, &ignored));
+    }
+
+    for (key, value) in options(&text, profile)? {
+        for flag in spec::config_flags() {
+            args.append(&mut flag.config_args(&key, &value));
+        }
+    }
+
+    instance_list.extend(instance_entries(&text).iter().map(|entry| host(entry)));
+    Ok(())
+}
+
+/// The `key = value` pairs of [default], with the ones from [profile.NAME] taking their place when a profile is given
+pub fn options(text: &str, profile: Option<&str>) -> Result<Vec<(String, String)>, String> {
+    let mut options = key_values(&section(text, "default").unwrap_or_default());
+    if let Some(profile) = profile {
+        let Some(overrides) = section(text, &format!("profile.{profile}")) else {
+            return Err(missing_profile(text, profile));
+        };
+        for (key, value) in key_values(&overrides) {
+            options.retain(|(option, _)| option != &key);
+            options.push((key, value));
+        }
+    }
+    Ok(options)
+}
+
+fn missing_profile(text: &str, profile: &str) -> String {
+    let known = profiles(text);
+    match known.is_empty() {
+        true => format!("The profile \"{profile}\" doesn't exist, the config file has no [profile.NAME] sections"),
+        false => format!("The profile \"{profile}\" doesn't exist (profiles in the config file: {})", known.join(", "))
+    }
+}
+
+/// The names of every [profile.NAME] section in the config file
+pub fn profiles(text: &str) -> Vec<String> {
+    text.lines().filter_map(|line| {
+        let name = crate::strings::remove_trailing_whitespace(line).strip_prefix("[profile.")?.strip_suffix(']')?.to_string();
+        (!name.is_empty()).then_some(name)
+    }).collect()
+}
+
+/// A mistake in the config file, and the line it is on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub line: usize,
+    pub reason: String,
+    /// The section the line is in, if it is in one tcobalt reads
+    pub section: Option<String>,
+    /// Whether the line sets a known key to a value its option doesn't take
+    pub invalid_value: bool
+}
+
+impl Problem {
+    /// Whether tcobalt can't run with the problem: an invalid value in [default] or in the profile being used.
+    /// Unknown keys and everything else only get a warning, and are left to `tcb config check`
+    pub fn blocks(&self, profile: Option<&str>) -> bool {
+        self.invalid_value && self.section.as_deref().is_some_and(|section| section == "default" || profile.is_some_and(|profile| section == format!("profile.{profile}")))
+    }
+}
+
+/// Finds every line of the config file that tcobalt can't make sense of:
+/// lines outside of a known section, lines that aren't `key = value`, unknown or repeated keys, and values the option doesn't take
+pub fn check(text: &str) -> Vec<Problem> {
+    let mut problems: Vec<Problem> = Vec::new();
+    let mut problem = |line: usize, reason: String, section: Option<&String>, invalid_value: bool| {
+        problems.push(Problem { line, reason, section: section.cloned(), invalid_value })
+    };
+
+    // instance numbers are checked against the list, which can come after them
+    let mut scratch = Args::get();
+    scratch.instance_list = instance_entries(text);
+
+    let mut sections: Vec<(String, usize)> = Vec::new();
+    let mut keys: Vec<(String, usize)> = Vec::new();
+    let mut current: Option<String> = None;
+    let mut seen_header = false;
+    for (idx, line) in text.lines().enumerate() {
+        let number = idx + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            let header = crate::strings::remove_trailing_whitespace(line);
+            seen_header = true;
+            keys.clear();
+            current = None;
+            let name = header.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or_default();
+            if name != "default" && name != "default.instances" && name.strip_prefix("profile.").is_none_or(|p| p.is_empty()) {
+                problem(number, format!("Unknown section {header}, expected [default], [default.instances] or [profile.NAME]"), None, false);
+            } else if let Some((_, first)) = sections.iter().find(|(s, _)| s == name) {
+                problem(number, format!("The section {header} is already on line {first}, so this one is ignored"), None, false);
+            } else {
+                sections.push((name.to_string(), number));
+                current = Some(name.to_string());
+            }
+            continue;
+        }
+        let Some(name) = &current else {
+            // the lines of a section that was already reported aren't reported again
+            if !seen_header {
+                problem(number, String::from("This line isn't inside a section, it should go under [default]"), None, false);
+            }
+            continue;
+        };
+        if name == "default.instances" {
+            if trimmed.contains(char::is_whitespace) {
+                problem(number, format!("An instance can't contain spaces: {trimmed}"), Some(name), false);
+            }
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            problem(number, format!("Expected `key = value`: {trimmed}"), Some(name), false);
+            continue;
+        };
+        let (key, value) = (key.trim().to_lowercase(), value.trim());
+        if let Some((_, first)) = keys.iter().find(|(k, _)| k == &key) {
+            problem(number, format!("The key {key} is already set on line {first}"), Some(name), false);
+            continue;
+        }
+        keys.push((key.clone(), number));
+        if let Err(reason) = check_value(&key, value, &mut scratch) {
+            let known = spec::config_defaults().iter().any(|(k, _)| *k == key);
+            problem(number, reason, Some(name), known);
+        }
+    }
+    problems
+}
 
-    if path.exists() {
-        let options = get_config(&path, "default");
-        for line in options.lines() {
-            let option: Vec<&str> = line.split('=').map(|s| s.trim()).collect();
-            if option.len() != 2 {
-                continue;
+/// Checks a value from the config file the same way it would be checked on the command line
+fn check_value(key: &str, value: &str, scratch: &mut Args) -> Result<(), String> {
+    let mut flags = spec::config_flags().filter(|flag| match flag.config {
+        spec::Config::Key(name) | spec::Config::When(name, _) => name == key,
+        spec::Config::None => false
+    }).peekable();
+    let Some(flag) = flags.peek().copied() else {
+        return Err(format!("Unknown key {key}, run `tcb help config` to see every key"));
+    };
+    match (flag.config, flag.value) {
+        (spec::Config::When(..), _) => {
+            let choices: Vec<&str> = flags.filter_map(|flag| match flag.config {
+                spec::Config::When(_, when) => Some(when),
+                _ => None
+            }).collect();
+            match choices.iter().any(|choice| choice.eq_ignore_ascii_case(value)) {
+                true => Ok(()),
+                false => Err(format!("Invalid value for {key}: {value} ({})", choices.join("/")))
             }
+        },
+        (_, spec::Value::Switch) if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") => Ok(()),
+        (_, spec::Value::Switch) => Err(format!("Invalid value for {key}: {value} (true/false)")),
+        _ if value.is_empty() || value.eq_ignore_ascii_case("none") => Ok(()),
+        (_, spec::Value::Choice(choices)) if flag.check(value).is_err() => Err(format!("Invalid value for {key}: {value} ({})", choices.join("/"))),
+        _ => {
+            let value = flag.check(value).unwrap_or(value.to_string());
+            (flag.apply)(scratch, &value).map_err(|e| format!("Invalid value for {key}: {e}"))
+        }
+    }
+}
 
-            match option[0].to_lowercase().as_str() {
-                "vcodec" => {
-                    args.push("-c".into());
-                    args.push(option[1].into())
-                },
-                "vquality" => {
-                    args.push("-q".into());
-                    args.push(option[1].into())
-                },
-                "aformat" => {
-                    args.push("-f".into());
-                    args.push(option[1].into())
-                },
-                "proxy" => {
-                    if option[1].to_lowercase().as_str() == "true" {
-                        args.push("-x".into())
-                    }
+/// One `path:line: reason` line for each problem, like a compiler would print them
+pub fn diagnostics(path: &Path, problems: &[Problem]) -> String {
+    problems.iter().map(|p| format!("{}:{}: {}", path.to_string_lossy(), p.line, p.reason.replace('\n', ". "))).collect::<Vec<String>>().join("\n")
+}
+
+/// Reads a single key from the [default] section of the config file, or from the profile if it sets it
+pub fn get_default(key: &str, profile: Option<&str>) -> Result<Option<String>, String> {
+    let text = std::fs::read_to_string(config_path()).unwrap_or_default();
+    Ok(options(&text, profile)?.into_iter().find_map(|(option, value)| (option == key).then_some(value)))
+}
+
+/// Rewrites the [default.instances] section of the config file with the given list.
+/// Without a config file, the instances come from the built-in list, so one is written like "tcb gen-config" would with them in it
+pub fn write_instances(instances: &[String]) -> std::io::Result<PathBuf> {
+    let path = config_path();
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
+            }
+            spec::default_config()
+        },
+        Err(e) => return Err(e)
+    };
+    std::fs::write(&path, replace_instances(&text, instances))?;
+    Ok(path)
+}
+
+/// Replaces the list in the [default.instances] section, putting the instances on the lines of the old ones in order.
+/// Comments and blank lines stay where they are, and every other section is left untouched
+pub fn replace_instances(text: &str, instances: &[String]) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut output: Vec<&str> = Vec::new();
+    let mut instances = instances.iter().map(|instance| instance.as_str());
+    match section_range(&lines, "default.instances") {
+        Some((start, end)) => {
+            let slots: Vec<usize> = (start + 1..end).filter(|idx| !lines[*idx].trim().is_empty() && !lines[*idx].trim_start().starts_with('#')).collect();
+            // instances that don't fit on the old lines go after the last of them
+            let last = slots.last().copied().unwrap_or(start);
+            for (idx, line) in lines.iter().enumerate() {
+                match slots.contains(&idx) {
+                    true => output.extend(instances.next()),
+                    false => output.push(line)
                 }
-                "download-mode" => {
-                    if option[1].to_lowercase().as_str() == "auto" {
-                        args.push("-=".into())
-                    } else if option[1].to_lowercase().as_str() == "audio" {
-                        args.push("-a".into())
-                    } else if option[1].to_lowercase().as_str() == "mute" {
-                        args.push("-m".into())
-                    }
-                },
-                "twitter-gif" => {
-                    if option[1].to_lowercase().as_str() == "true" {
-                        args.push("-g".into())
-                    }
-                },
-                "tt-full-audio" => {
-                    if option[1].to_lowercase().as_str() == "true" {
-                        args.push("-u".into())
-                    }
-                },
-                "tt-h265" => {
-                    if option[1].to_lowercase().as_str() == "true" {
-                        args.push("-h".into())
-                    }
-                },
-                "dublang" => {
-                    if option[1].to_lowercase().as_str() != "none" {
-                        args.push("-l".into());
-                        args.push(option[1].into())
-                    }
-                },
-                "no-metadata" => {
-                    if option[1].to_lowercase().as_str() == "true" {
-                        args.push("-n".into())
-                    }
-                },
-                "fname-style" => {
-                    args.push("-s".into());
-                    args.push(option[1].into())
-                },
-                "instance" => {
-                    args.push("-i".into());
-                    args.push(option[1].into())
-                },
-                "bitrate" => {
-                    args.push("-b".into());
-                    args.push(option[1].into())
+                if idx == last {
+                    output.extend(instances.by_ref());
                 }
-                _ => ()
             }
+        },
+        None => {
+            output = lines;
+            if output.last().is_some_and(|line| !line.trim().is_empty()) {
+                output.push("");
+            }
+            output.push("[default.instances]");
+            output.extend(instances);
+        }
+    }
+    output.join("\n") + "\n"
+}
+
+/// The entries of the [default.instances] section, as they are written in the file
+pub fn instance_entries(text: &str) -> Vec<String> {
+    section(text, "default.instances").unwrap_or_default().lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|line| line.trim().to_string())
+        .collect()
+}
+
+/// The bare host of an instance url, like the -i option takes it
+pub fn host(instance: &str) -> String {
+    let mut url = instance.replace("https://", "");
+    if let Some(idx) = url.find('/') {
+        url.truncate(idx);
+    }
+    url
+}
+
+/// Where the value of a key comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// The section, the line number it is set on and the value as it is written there
+    File(String, usize, String),
+    /// The key isn't set anywhere, so the option keeps the value it has without a config file
+    BuiltIn
+}
+
+/// The value a key ends up with, the way the option reads it, and where it comes from.
+/// The profile's own keys win over [default], which wins over the built-in defaults
+pub fn lookup(text: &str, profile: Option<&str>, key: &str) -> Result<(String, Source), String> {
+    let Some((_, default)) = spec::config_defaults().into_iter().find(|(k, _)| *k == key) else {
+        return Err(format!("Unknown key {key}, run `tcb help config` to see every key"));
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    let mut symbols = vec![String::from("default")];
+    if let Some(profile) = profile {
+        if section_range(&lines, &format!("profile.{profile}")).is_none() {
+            return Err(missing_profile(text, profile));
         }
-        drop(options);
+        symbols.insert(0, format!("profile.{profile}"));
+    }
+    for symbol in symbols {
+        let Some(range) = section_range(&lines, &symbol) else {
+            continue;
+        };
+        if let Some(&idx) = key_lines(&lines, range, key).first() {
+            let value = lines[idx].split_once('=').map(|(_, v)| v.trim()).unwrap_or_default();
+            return Ok((effective(text, key, value), Source::File(symbol, idx + 1, value.to_string())));
+        }
+    }
+    Ok((default.to_string(), Source::BuiltIn))
+}
 
-        let instances = get_config(&path, "default.instances");
-        for line in instances.lines() {
-            let mut url = line.replace("https://", "");
-            if let Some(idx) = url.find('/') {
-                url.truncate(idx);
+/// A value from the config file the way the option ends up reading it:
+/// abbreviated choices spelled out, true/false in lowercase and instance numbers turned into the instance they pick
+fn effective(text: &str, key: &str, value: &str) -> String {
+    let mut flags = spec::config_flags().filter(|flag| match flag.config {
+        spec::Config::Key(name) | spec::Config::When(name, _) => name == key,
+        spec::Config::None => false
+    });
+    let Some(flag) = flags.next() else {
+        return value.to_string();
+    };
+    match (flag.config, flag.value) {
+        (spec::Config::When(_, when), _) => std::iter::once(when).chain(flags.filter_map(|flag| match flag.config {
+            spec::Config::When(_, when) => Some(when),
+            _ => None
+        })).find(|when| when.eq_ignore_ascii_case(value)).unwrap_or(value).to_string(),
+        (_, spec::Value::Switch) => value.to_lowercase(),
+        _ if value.is_empty() || value.eq_ignore_ascii_case("none") => value.to_string(),
+        (_, spec::Value::Choice(_)) => flag.check(value).unwrap_or(value.to_string()),
+        (_, spec::Value::Instance) => match value.parse::<usize>() {
+            Ok(choice) => choice.checked_sub(1).and_then(|idx| instance_entries(text).get(idx).map(|entry| host(&entry.to_lowercase()))).unwrap_or(value.to_string()),
+            Err(_) => host(&value.to_lowercase())
+        },
+        _ => value.to_string()
+    }
+}
+
+/// Sets a key in a section, changing the line that sets it if there is one, or adding a line to the end of the section.
+/// The section is added to the end of the file if it isn't there. Every other line, comments included, stays as it is
+pub fn set_key(text: &str, symbol: &str, key: &str, value: &str) -> String {
+    let mut lines: Vec<&str> = text.lines().collect();
+    let (header, entry) = (format!("[{symbol}]"), format!("{key} = {value}"));
+    match section_range(&lines, symbol) {
+        Some(range) => match key_lines(&lines, range, key).as_slice() {
+            [first, repeated @ ..] => {
+                lines[*first] = &entry;
+                for idx in repeated.iter().rev() {
+                    lines.remove(*idx);
+                }
+            },
+            [] => {
+                // after the last line that isn't blank, so the blank lines before the next section stay there
+                let last = (range.0..range.1).rev().find(|idx| !lines[*idx].trim().is_empty()).unwrap_or(range.0);
+                lines.insert(last + 1, &entry);
             }
-            instance_list.push(format!("{url}\n"));
+        },
+        None => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                lines.push("");
+            }
+            lines.extend([header.as_str(), entry.as_str()]);
         }
     }
+    lines.join("\n") + "\n"
 }
 
-fn get_config(path: &PathBuf, symbol: &str) -> String {
-    let text = std::fs::read_to_string(path).unwrap_or("".into());
+/// Removes every line that sets a key in a section, or returns None if the section doesn't set it
+pub fn unset_key(text: &str, symbol: &str, key: &str) -> Option<String> {
+    let mut lines: Vec<&str> = text.lines().collect();
+    let found = key_lines(&lines, section_range(&lines, symbol)?, key);
+    if found.is_empty() {
+        return None;
+    }
+    for idx in found.iter().rev() {
+        lines.remove(*idx);
+    }
+    Some(lines.join("\n") + "\n")
+}
 
-    let mut string = String::new();
-    let mut select = false;
-    let mut brackets = 0;
+/// Runs a "tcb config" action on the config file, returning what to print
+pub fn run(action: &types::ConfigAction, profile: Option<&str>) -> Result<String, String> {
+    let path = config_path();
+    let shown = path.to_string_lossy().to_string();
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        // every other action works the same on a file that doesn't exist yet as on an empty one
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && *action != types::ConfigAction::Check => String::new(),
+        Err(e) => return Err(format!("Unable to read the config file {shown}: {e}\nRun `tcb gen-config` to write the default one"))
+    };
+    let write = |text: &str| std::fs::write(&path, text).map_err(|e| format!("Unable to write the config file {shown}: {e}"));
+    // every action but "check" works on the same profile every other command would use, so set and unset change what get shows
+    let profile = profile.map(String::from).or_else(env_profile);
+    let symbol = profile.as_ref().map_or(String::from("default"), |profile| format!("profile.{profile}"));
+    // the value as it is written only shows up when it reads differently, like an abbreviation or an instance number
+    let describe = |value: &str, source: Source| match source {
+        Source::File(symbol, line, written) if written != value => format!("{shown}:{line}, [{symbol}], written as {written}"),
+        Source::File(symbol, line, _) => format!("{shown}:{line}, [{symbol}]"),
+        Source::BuiltIn => String::from("not set, built-in default")
+    };
 
-    for line in text.lines().into_iter() {
-        if brackets == 2 { break }
-        if !select && crate::strings::remove_trailing_whitespace(line) != format!("[{symbol}]") { continue }
-        select = true;
-        if line.chars().collect::<Vec<char>>().first() == Some(&'[') { brackets += 1; continue }
+    match action {
+        types::ConfigAction::Check => {
+            let problems = check(&text);
+            if !problems.is_empty() {
+                return Err(format!("{}\nFound {} problem(s) in {shown}", diagnostics(&path, &problems), problems.len()));
+            }
+            let profiles = profiles(&text);
+            Ok(match profiles.is_empty() {
+                true => format!("{shown} has no problems"),
+                false => format!("{shown} has no problems (profiles: {})", profiles.join(", "))
+            })
+        },
+        types::ConfigAction::List => {
+            let mut entries: Vec<(String, String)> = Vec::new();
+            for (key, _) in spec::config_defaults() {
+                let (value, source) = lookup(&text, profile.as_deref(), key)?;
+                entries.push((format!("{key} = {value}"), describe(&value, source)));
+            }
+            let width = entries.iter().map(|(entry, _)| entry.len()).max().unwrap_or(0);
+            let mut output: Vec<String> = profile.iter().map(|profile| format!("With the profile {profile}:")).collect();
+            output.extend(entries.iter().map(|(entry, source)| format!("{entry:<width$}   ({source})")));
+            Ok(output.join("\n"))
+        },
+        types::ConfigAction::Get(key) => {
+            let (value, source) = lookup(&text, profile.as_deref(), key)?;
+            Ok(format!("{key} = {value}   ({})", describe(&value, source)))
+        },
+        types::ConfigAction::Set(key, value) => {
+            let mut scratch = Args::get();
+            scratch.instance_list = instance_entries(&text);
+            check_value(key, value, &mut scratch)?;
+            write(&set_key(&text, &symbol, key, value))?;
+            Ok(format!("Set {key} = {value} in [{symbol}] of {shown}"))
+        },
+        types::ConfigAction::Unset(key) => {
+            let Some(unset) = unset_key(&text, &symbol, key) else {
+                return Err(format!("{key} isn't set in the [{symbol}] section of {shown}"));
+            };
+            write(&unset)?;
+            Ok(format!("Removed {key} from [{symbol}] of {shown}"))
+        },
+        types::ConfigAction::AddInstance(instance, position) => {
+            let mut entries = instance_entries(&text);
+            if instance.is_empty() || instance.contains(char::is_whitespace) {
+                return Err(format!("Invalid instance: {instance}"));
+            }
+            if let Some(idx) = entries.iter().position(|entry| host(entry) == host(instance)) {
+                return Err(format!("{} is already instance {}", host(instance), idx + 1));
+            }
+            let at = position.unwrap_or(entries.len() + 1);
+            if at > entries.len() + 1 {
+                return Err(format!("Invalid position: {at} (there are {} instances)", entries.len()));
+            }
+            entries.insert(at - 1, instance.clone());
+            write(&replace_instances(&text, &entries))?;
+            Ok(format!("Added {instance} as instance {at}"))
+        },
+        types::ConfigAction::RemoveInstance(instance) => {
+            let mut entries = instance_entries(&text);
+            let idx = find_instance(&entries, instance)?;
+            let removed = entries.remove(idx);
+            write(&replace_instances(&text, &entries))?;
+            Ok(format!("Removed instance {} ({removed}), the numbers of the instances after it went down by one", idx + 1))
+        },
+        types::ConfigAction::MoveInstance(instance, to) => {
+            let mut entries = instance_entries(&text);
+            let idx = find_instance(&entries, instance)?;
+            if *to > entries.len() {
+                return Err(format!("Invalid position: {to} (there are {} instances)", entries.len()));
+            }
+            let moved = entries.remove(idx);
+            entries.insert(to - 1, moved.clone());
+            write(&replace_instances(&text, &entries))?;
+            Ok(format!("Moved {moved} from instance {} to {to}", idx + 1))
+        }
+    }
+}
 
-        string.push_str(line);
-        string.push('\n');
+/// The index of an instance in [default.instances], given as its number or its url
+fn find_instance(entries: &[String], instance: &str) -> Result<usize, String> {
+    match instance.parse::<usize>() {
+        Ok(choice) => choice.checked_sub(1).filter(|idx| *idx < entries.len()).ok_or(String::from("Invalid instance quick-choice")),
+        Err(_) => entries.iter().position(|entry| host(entry) == host(instance))
+            .ok_or(format!("{} isn't in the [default.instances] section", host(instance)))
     }
-    crate::strings::remove_trailing_whitespace(string)
 }
 
+/// The index of a section's [header] line, and of the line after its last line
+fn section_range(lines: &[&str], symbol: &str) -> Option<(usize, usize)> {
+    let header = format!("[{symbol}]");
+    let start = lines.iter().position(|line| crate::strings::remove_trailing_whitespace(line) == header)?;
+    let end = (start + 1..lines.len()).find(|idx| lines[*idx].starts_with('[')).unwrap_or(lines.len());
+    Some((start, end))
+}
+
+/// The indexes of the lines in a section that set a key
+fn key_lines(lines: &[&str], (start, end): (usize, usize), key: &str) -> Vec<usize> {
+    (start + 1..end).filter(|idx| lines[*idx].split_once('=').is_some_and(|(k, _)| k.trim().eq_ignore_ascii_case(key))).collect()
+}
+
+/// The lines of a [section] of the config file, or None if it isn't in there
+fn section(text: &str, symbol: &str) -> Option<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let (start, end) = section_range(&lines, symbol)?;
+    Some(crate::strings::remove_trailing_whitespace(lines[start + 1..end].join("\n")))
+}
+
+fn key_values(section: &str) -> Vec<(String, String)> {
+    section.lines().filter_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        Some((key.trim().to_lowercase(), value.trim().to_string()))
+    }).collect()
+}
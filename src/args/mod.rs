@@ -1,7 +1,9 @@
 use std::io::Read;
 
 pub mod types;
-mod config;
+pub mod config;
+pub mod bulk;
+pub mod spec;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Args {
@@ -10,6 +12,9 @@ pub struct Args {
     pub method: Option<types::Method>,
     pub c_url: Option<String>,
     pub bulk_array: Option<Vec<Args>>,
+    pub bulk_lines: Vec<String>,
+    pub bulk_duplicates: Vec<(String, String)>,
+    pub retry_file: Option<String>,
     pub c_video_codec: types::VideoCodec,
     pub c_video_quality: u16,
     pub c_audio_format: types::AudioFormat,
@@ -21,12 +26,26 @@ pub struct Args {
     pub c_disable_metadata: bool,
     pub accept_language: String,
     pub out_filename: Option<String>,
+    pub output_dir: Option<String>,
+    pub stdout: bool,
+    pub dry_run: bool,
+    pub exec: Option<String>,
+    pub if_exists: types::Collision,
     pub c_fname_style: types::FilenamePattern,
     pub same_filenames: bool,
     pub picker_choice: u8,
     pub cobalt_instance: String,
+    pub instance_list: Vec<String>,
+    pub failover: bool,
+    pub json_output: bool,
+    pub reorder_instances: bool,
+    pub service_flags: Vec<String>,
+    pub warnings: Vec<String>,
+    pub archive: Option<String>,
+    pub force: bool,
     pub help_flag: Option<types::Help>,
-    pub c_proxy: bool
+    pub c_proxy: bool,
+    pub profile: Option<String>
 }
 impl Args {
     pub fn get() -> Self {
@@ -40,11 +59,19 @@ impl Args {
             c_download_mode: types::DownloadMode::Auto,
             c_twitter_gif: false,
             out_filename: None,
+            output_dir: None,
+            stdout: false,
+            dry_run: false,
+            exec: None,
+            if_exists: types::Collision::Rename,
             same_filenames: false,
             c_audio_bitrate: 128,
             help_flag: None,
             method: None,
             bulk_array: None,
+            bulk_lines: Vec::new(),
+            bulk_duplicates: Vec::new(),
+            retry_file: None,
             picker_choice: 0,
             c_fname_style: types::FilenamePattern::Classic,
             c_tt_full_audio: false,
@@ -52,26 +79,28 @@ impl Args {
             c_disable_metadata: false,
             c_proxy: false,
             cobalt_instance: String::from("api.cobalt.tools"),
+            instance_list: Vec::new(),
+            failover: false,
+            json_output: false,
+            reorder_instances: false,
+            service_flags: Vec::new(),
+            warnings: Vec::new(),
+            archive: None,
+            force: false,
+            profile: None,
             accept_language: String::from("en")
         }
     }
 
     pub fn parse(mut self) -> Result<Self, types::ParseError> {
         self.parsed = true;
-        self.raw = self.raw.iter().map(|str| {
-            if !str.contains("https://") {
-                str.to_lowercase()
-            } else {
-                str.into()
-            }
-        }).collect();
 
-        match self.raw.get(1) {
+        match self.raw.get(1).map(|m| m.to_lowercase()) {
             Some(method) => match method.as_str() {
                 "help" | "-h" | "--help" | "h" => {
                     self.method = Some(types::Method::Help);
                     match self.raw.get(2) {
-                        Some(help) => match help.as_str() {
+                        Some(help) => match help.to_lowercase().as_str() {
                             "get" | "g" => self.help_flag = Some(types::Help::Get),
                             "list" | "l" => self.help_flag = Some(types::Help::List),
                             "bulk" | "b" => self.help_flag = Some(types::Help::Bulk),
@@ -79,165 +108,61 @@ impl Args {
                             "examples" | "e" => self.help_flag = Some(types::Help::Examples),
                             "gen-config" | "gc" => self.help_flag = Some(types::Help::GenConfig),
                             "config" | "c" => self.help_flag = Some(types::Help::Config),
+                            "instances" | "i" => self.help_flag = Some(types::Help::Instances),
+                            "url" | "u" => self.help_flag = Some(types::Help::Url),
+                            "info" => self.help_flag = Some(types::Help::Info),
+                            "completions" => self.help_flag = Some(types::Help::Completions),
+                            "man" => self.help_flag = Some(types::Help::Man),
+                            "environment" | "env" => self.help_flag = Some(types::Help::Environment),
                             _ => self.help_flag = Some(types::Help::Help)
                         },
                         None => self.help_flag = Some(types::Help::Help),
                     }
                 },
-                "get" | "g" => {
-                    self.method = Some(types::Method::Get);
+                "get" | "g" | "url" | "u" | "info" => {
+                    self.method = Some(match method.as_str() {
+                        "url" | "u" => types::Method::Url,
+                        "info" => types::Method::Info,
+                        _ => types::Method::Get
+                    });
 
+                    let tables: &[&'static [spec::Flag]] = match self.method {
+                        Some(types::Method::Url) => &[spec::GET_FLAGS, spec::SERVICE_FLAGS, spec::URL_FLAGS],
+                        Some(types::Method::Info) => &[spec::GET_FLAGS, spec::SERVICE_FLAGS],
+                        _ => &[spec::GET_FLAGS, spec::SERVICE_FLAGS, spec::DOWNLOAD_FLAGS]
+                    };
+                    // mistakes in the arguments are reported by the full parse below, after the config file is loaded
+                    let profile = spec::parse(tables, &self.raw[2..]).unwrap_or_default().into_iter().rev().find_map(|(_, parsed)| match parsed {
+                        spec::Parsed::Flag(flag, value) if flag.long == "profile" => Some(value),
+                        _ => None
+                    }).or_else(config::env_profile);
                     let mut instance_list: Vec<String> = Vec::new();
                     let mut default_args: Vec<String> = Vec::new();
-                    config::load_config_into(&mut default_args, &mut instance_list);
+                    config::load_config_into(&mut default_args, &mut instance_list, profile.as_deref()).map_err(|e| types::ParseError::throw_config(&e))?;
                     let (pre_args, added_args) = self.raw.split_at(2);
                     self.raw = [pre_args, &default_args, added_args].concat().to_vec();
 
-                    let mut idx = 1;
-                    let mut expected: Vec<ExpectedFlags> = Vec::new();
+                    self.instance_list = instance_list;
                     let mut stdin = false;
-                    while let Some(arg) = self.raw.get(idx+1) {
-                        idx += 1;
-                        if expected.len() == 0 {
-                            let mut short = false;
-                            match arg.as_str() {
-                                "--vcodec" => expected.push(ExpectedFlags::VideoCodec),
-                                "--vquality" => expected.push(ExpectedFlags::VideoQuality),
-                                "--aformat" => expected.push(ExpectedFlags::AudioFormat),
-                                "--audio-only" => self.c_download_mode = types::DownloadMode::Audio,
-                                "--mute-audio" => self.c_download_mode = types::DownloadMode::Mute,
-                                "--auto" => self.c_download_mode = types::DownloadMode::Auto,
-                                "--twitter-gif" => self.c_twitter_gif = !self.c_twitter_gif,
-                                "--tt-full-audio" => self.c_tt_full_audio = !self.c_tt_full_audio,
-                                "--tt-h265" => self.c_tt_h265 = !self.c_tt_h265,
-                                "--dublang" => expected.push(ExpectedFlags::Language),
-                                "--no-metadata" => self.c_disable_metadata = !self.c_disable_metadata,
-                                "--output" => expected.push(ExpectedFlags::Output),
-                                "--fname-style" => expected.push(ExpectedFlags::FilenamePattern),
-                                "--pick" => expected.push(ExpectedFlags::Picker),
-                                "--instance" => expected.push(ExpectedFlags::Instance),
-                                "--bitrate" => expected.push(ExpectedFlags::Bitrate),
-                                "--proxy" => self.c_proxy = !self.c_proxy,
-                                _ => {
-                                    if self.c_url == None && arg.contains("https://") {
-                                        self.c_url = Some(arg.clone());
-                                        continue;
-                                    }
-                                    if self.c_url.is_some() && arg.contains("https://") {
-                                        return Err(types::ParseError::throw_invalid("You cannot have 2 URLs in the same GET command"));
-                                    }
-                                    for c in arg.chars() {
-                                        if !short {
-                                            if c == '-' {
-                                                short = true;
-                                            } else if c == '+' {
-                                                stdin = true;
-                                            } else {
-                                                return Err(types::ParseError::throw_invalid(&format!("Unrecognized argument: {arg}")));
-                                            }
-                                            continue;
-                                        }
-                                        match c {
-                                            'c' => expected.push(ExpectedFlags::VideoCodec),
-                                            'q' => expected.push(ExpectedFlags::VideoQuality),
-                                            'f' => expected.push(ExpectedFlags::AudioFormat),
-                                            'a' => self.c_download_mode = types::DownloadMode::Audio,
-                                            'm' => self.c_download_mode = types::DownloadMode::Mute,
-                                            'g' => self.c_twitter_gif = !self.c_twitter_gif,
-                                            'u' => self.c_tt_full_audio = !self.c_tt_full_audio,
-                                            'h' => self.c_tt_h265 = !self.c_tt_h265,
-                                            'l' => {
-                                                expected.push(ExpectedFlags::Language);
-                                            },
-                                            'n' => self.c_disable_metadata = !self.c_disable_metadata,
-                                            'o' => expected.push(ExpectedFlags::Output),
-                                            's' => expected.push(ExpectedFlags::FilenamePattern),
-                                            'p' => expected.push(ExpectedFlags::Picker),
-                                            'i' => expected.push(ExpectedFlags::Instance),
-                                            'x' => self.c_proxy = !self.c_proxy,
-                                            '=' => self.c_download_mode = types::DownloadMode::Auto,
-                                            'b' => expected.push(ExpectedFlags::Bitrate),
-                                            _ => return Err(types::ParseError::throw_invalid(&format!("Invalid character {c} in multi-flag argument: {arg}")))
-                                        }
-                                    }
+                    for (idx, parsed) in spec::parse(tables, &self.raw[2..])? {
+                        match parsed {
+                            spec::Parsed::Flag(flag, value) => {
+                                // flags from the config file apply to every link, so only warn about the ones given on the command line
+                                let name = format!("--{}", flag.long);
+                                if idx >= default_args.len() && !crate::service::flag_services(&name).is_empty() && !self.service_flags.contains(&name) {
+                                    self.service_flags.push(name);
                                 }
-                            }
-                        } else {
-                            match expected.remove(0) {
-                                ExpectedFlags::VideoCodec => {
-                                    match arg.as_str() {
-                                        "h264" => self.c_video_codec = types::VideoCodec::H264,
-                                        "av1" => self.c_video_codec = types::VideoCodec::AV1,
-                                        "vp9" => self.c_video_codec = types::VideoCodec::VP9,
-                                        _ => return Err(types::ParseError::throw_invalid(&format!("Invalid video codec: {arg}")))
-                                    }
-                                },
-                                ExpectedFlags::VideoQuality => {
-                                    match arg.as_str() {
-                                        "144" | "480" | "720" | "1080" | "1440" | "2160" => self.c_video_quality = arg.parse().unwrap(),
-                                        _ => return Err(types::ParseError::throw_invalid(&format!("Invalid video quality: {arg}")))
-                                    }
-                                },
-                                ExpectedFlags::AudioFormat => {
-                                    match arg.as_str() {
-                                        "best" => self.c_audio_format = types::AudioFormat::BEST,
-                                        "mp3" => self.c_audio_format = types::AudioFormat::MP3,
-                                        "ogg" => self.c_audio_format = types::AudioFormat::OGG,
-                                        "wav" => self.c_audio_format = types::AudioFormat::WAV,
-                                        "opus" => self.c_audio_format = types::AudioFormat::OPUS,
-                                        _ => return Err(types::ParseError::throw_invalid(&format!("Invalid audio format: {arg}")))
-                                    }
-                                },
-                                ExpectedFlags::Output => {
-                                    if arg.contains(".mp3") || arg.contains(".ogg") || arg.contains(".wav") || arg.contains(".opus") || arg.contains(".mp4") || arg.contains(".webm") || arg.contains(".gif") {
-                                        self.out_filename = Some(arg.clone())
-                                    } else {
-                                        return Err(types::ParseError::throw_invalid("Output filename must be a video file type (supported: mp4/webm/gif), or an audio file type (supported: mp3/ogg/wav/opus)\nMake sure you choose the right file type for the chosen codec/format!"));
-                                    }
-                                },
-                                ExpectedFlags::FilenamePattern => {
-                                    match arg.as_str() {
-                                        "classic" | "c" => self.c_fname_style = types::FilenamePattern::Classic,
-                                        "pretty" | "p" => self.c_fname_style = types::FilenamePattern::Pretty,
-                                        "basic" | "b" => self.c_fname_style = types::FilenamePattern::Basic,
-                                        "nerdy" | "n" => self.c_fname_style = types::FilenamePattern::Nerdy,
-                                        _ => return Err(types::ParseError::throw_invalid(&format!("Invalid filename style: {arg}")))
-                                    }
-                                },
-                                ExpectedFlags::Picker => {
-                                    if let Ok(int) = arg.parse::<u8>() {
-                                        self.picker_choice = int;
-                                    } else {
-                                        return Err(types::ParseError::throw_invalid("Picker choice must be an integer between 0 and 255"));
-                                    }
-                                },
-                                ExpectedFlags::Language => {
-                                    self.accept_language = arg.clone();
-                                },
-                                ExpectedFlags::Instance => {
-                                    let mut url = if let Ok(choice) = arg.parse::<u8>() {
-                                        if let Some(url) = instance_list.get((choice-1) as usize) {
-                                            url.clone()
-                                        } else {
-                                            return Err(types::ParseError::throw_invalid("Invalid instance quick-choice"))
-                                        }
-                                    } else {
-                                        arg.clone()
-                                    };
-                                    url = url.replace("https://", "");
-                                    if let Some(idx) = url.find('/') {
-                                        url.truncate(idx);
-                                    }
-                                    self.cobalt_instance = url;
-                                },
-                                ExpectedFlags::Bitrate => {
-                                    if arg == "320" || arg == "256" || arg == "128" || arg == "96" || arg == "64" || arg == "8" {
-                                        self.c_audio_bitrate = arg.parse::<u16>().unwrap();
-                                    } else {
-                                        return Err(types::ParseError::throw_invalid("Make sure you select a valid bitrate! (320/256/128/96/64/8)"));
-                                    }
+                                let value = flag.check(&value)?;
+                                (flag.apply)(&mut self, &value).map_err(|e| types::ParseError::throw_invalid(&e))?;
+                            },
+                            spec::Parsed::Positional(arg) if arg.contains("https://") => {
+                                if self.c_url.is_some() {
+                                    return Err(types::ParseError::throw_invalid("You cannot have 2 URLs in the same GET command"));
                                 }
-                            }
+                                self.c_url = Some(arg);
+                            },
+                            spec::Parsed::Positional(arg) if arg == "+" => stdin = true,
+                            spec::Parsed::Positional(arg) => return Err(types::ParseError::throw_invalid(&format!("Unrecognized argument: {arg}")))
                         }
                     }
                     if stdin {
@@ -255,51 +180,84 @@ impl Args {
                     if self.c_url == None {
                         return Err(types::ParseError::throw_incomplete("Missing URL from GET method"))
                     }
-                    if expected.len() > 0 {
-                        let mut missing = String::new();
-                        for (i, v) in expected.iter().enumerate() {
-                            if i > 0 {
-                                missing.push_str(", ")
-                            }
-                            missing.push_str(&format!("{v:?}"));
-                        }
-                        return Err(types::ParseError::throw_incomplete(&format!("The following flags were specified but their values were not: {missing}")));
-                    }
                 },
                 "bulk" | "b" => {
-                    if let Some(action) = self.raw.get(2) {
+                    if let Some(action) = self.raw.get(2).map(|a| a.to_lowercase()) {
                         self.method = Some(types::Method::Bulk);
+                        // otherwise a broken config file would be reported as a mistake in the first link or line
+                        config::load_config_into(&mut Vec::new(), &mut Vec::new(), None).map_err(|e| types::ParseError::throw_config(&e))?;
                         match action.as_str() {
                             "get" | "g" => {
+                                let tokens = self.raw.get(3..).unwrap_or_default().to_vec();
+                                let parsed = match spec::parse(&[spec::GET_FLAGS, spec::SERVICE_FLAGS, spec::DOWNLOAD_FLAGS, spec::BULK_GET_FLAGS], &tokens) {
+                                    Ok(parsed) => parsed,
+                                    Err(e) => return Err(types::ParseError::throw_bulkerr(&format!("Invalid flags | {}", e.print())))
+                                };
+                                // the links and the options of "bulk get" itself are taken out, and everything else is passed on to "get"
+                                let mut taken: Vec<usize> = Vec::new();
                                 let mut url_list: Vec<String> = Vec::new();
-                                let mut dummy_args = self.raw.clone();
-                                let mut has_url = false;
-                                (0..dummy_args.len()).rev().for_each(|i| {
-                                    if dummy_args[i].contains("https://") {
-                                        has_url = true;
-                                        url_list.push(dummy_args[i].clone());
-                                        dummy_args.remove(i);
+                                let mut listed: Vec<String> = Vec::new();
+                                let mut stdin = false;
+                                for (idx, parsed) in parsed {
+                                    match parsed {
+                                        spec::Parsed::Flag(flag, value) if spec::BULK_GET_FLAGS.iter().any(|f| f.long == flag.long) => {
+                                            taken.push(idx);
+                                            if !tokens[idx].contains('=') {
+                                                taken.push(idx + 1);
+                                            }
+                                            if flag.long == "retry-file" {
+                                                self.retry_file = Some(value);
+                                                continue;
+                                            }
+                                            match std::fs::read_to_string(&value) {
+                                                Ok(contents) => listed.append(&mut bulk::parse_url_list(&contents)?),
+                                                Err(_) => return Err(types::ParseError::throw_invalid(&format!("The file \"{value}\" either doesnt exist, or doesn't have proper permissions")))
+                                            }
+                                        },
+                                        spec::Parsed::Flag(..) => (),
+                                        spec::Parsed::Positional(arg) if arg.contains("https://") => {
+                                            taken.push(idx);
+                                            url_list.push(arg);
+                                        },
+                                        spec::Parsed::Positional(arg) if arg == "+" => {
+                                            taken.push(idx);
+                                            stdin = true;
+                                        },
+                                        spec::Parsed::Positional(arg) => return Err(types::ParseError::throw_bulkerr(&format!("Invalid flags | Unrecognized argument: {arg}")))
                                     }
-                                });
-                                if !has_url {
+                                }
+                                // links given on the command line come first and file lists after them, each in the order they were given
+                                url_list.append(&mut listed);
+                                if stdin {
+                                    let mut buf = String::new();
+                                    std::io::stdin().read_to_string(&mut buf).unwrap_or(0);
+                                    url_list.append(&mut bulk::parse_url_list(&buf)?);
+                                }
+                                let dummy_args: Vec<String> = tokens.iter().enumerate().filter(|(i, _)| !taken.contains(i)).map(|(_, t)| t.clone()).collect();
+                                if url_list.is_empty() {
                                     return Err(types::ParseError::throw_incomplete("Bulk get action is missing at least 1 URL"));
                                 }
-                                (0..=2).for_each(|_| {
-                                    dummy_args.remove(0);
-                                });
                                 let get_flags = dummy_args.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
                                 let get_flags = Self::override_args(&[&["get", "https://"], get_flags.as_slice()].concat()).parse();
                                 match get_flags {
                                     Ok(flags) => {
+                                        if flags.stdout {
+                                            return Err(types::ParseError::throw_bulkerr("Writing to stdout with -o - can't be used with bulk downloads"));
+                                        }
                                         let mut arg_array: Vec<Self> = Vec::new();
+                                        let mut labels: Vec<String> = Vec::new();
+                                        let mut lines: Vec<String> = Vec::new();
                                         for url in url_list {
+                                            labels.push(url.clone());
+                                            lines.push(std::iter::once(&url).chain(dummy_args.iter()).map(|a| bulk::quote(a)).collect::<Vec<String>>().join(" "));
                                             arg_array.push({
                                                 let mut clone = flags.clone();
                                                 clone.c_url = Some(url);
                                                 clone
                                             });
                                         }
-                                        self.bulk_array = Some(arg_array);
+                                        self.bulk_array = Some(self.dedup_bulk(arg_array, labels, lines));
+                                        self.dry_run = flags.dry_run;
                                         if flags.out_filename.is_some() {
                                             self.same_filenames = true;
                                         }
@@ -308,20 +266,50 @@ impl Args {
                                 }
                             },
                             "execute" | "exe" | "e" => {
-                                if let Some(filename) = self.raw.get(3) {
-                                    if let Ok(contents) = std::fs::read_to_string(filename) {
+                                let args = self.raw.get(3..).unwrap_or_default().to_vec();
+                                let mut positionals = self.apply_flags(&[spec::BULK_EXECUTE_FLAGS], &args)?.into_iter();
+                                if let Some(filename) = positionals.next() {
+                                    if let Some(arg) = positionals.next() {
+                                        return Err(types::ParseError::throw_invalid(&format!("Unrecognized argument: {arg}")));
+                                    }
+                                    let contents = if filename == "-" {
+                                        let mut buf = String::new();
+                                        std::io::stdin().read_to_string(&mut buf).map(|_| buf)
+                                    } else {
+                                        std::fs::read_to_string(&filename)
+                                    };
+                                    if let Ok(contents) = contents {
                                         let mut arg_array: Vec<Self> = Vec::new();
-                                        for (i, line) in contents.lines().enumerate() {
-                                            let mut args_raw = line.split(" ").collect::<Vec<&str>>();
-                                            args_raw.insert(0, "get");
+                                        let mut labels: Vec<String> = Vec::new();
+                                        let mut lines: Vec<String> = Vec::new();
+                                        for line in bulk::parse_file(&contents)? {
+                                            lines.push(line.standalone());
+                                            labels.push(format!("line {} ({})", line.number, line.tokens.iter().find(|a| a.contains("https://")).unwrap_or(&line.text)));
+                                            let mut args_raw = vec!["get"];
+                                            args_raw.extend(line.defaults.iter().chain(line.tokens.iter()).map(|t| t.as_str()));
                                             match Self::override_args(args_raw.as_slice()).parse() {
-                                                Ok(args) => arg_array.push(args),
-                                                Err(e) => return Err(types::ParseError::throw_bulkerr(&format!("On line {} | {}", i+1, e.print())))
+                                                Ok(args) if args.stdout => return Err(types::ParseError::throw_bulkerr(&bulk::line_error(line.number, None, "Writing to stdout with -o - can't be used with bulk downloads", &line.text))),
+                                                Ok(mut args) => {
+                                                    args.dry_run = args.dry_run || self.dry_run;
+                                                    // like config defaults, directives apply to every line, so only warn about the line's own flags
+                                                    if !line.defaults.is_empty() {
+                                                        let mut own = vec!["get", "https://"];
+                                                        own.extend(line.tokens.iter().filter(|t| !t.contains("https://")).map(|t| t.as_str()));
+                                                        if let Ok(own) = Self::override_args(&own).parse() {
+                                                            args.service_flags = own.service_flags;
+                                                        }
+                                                    }
+                                                    arg_array.push(args)
+                                                },
+                                                Err(e) => {
+                                                    let col = bulk::error_column(&line.text, &line.tokens, &e.print());
+                                                    return Err(types::ParseError::throw_bulkerr(&bulk::line_error(line.number, col, &e.print(), &line.text)))
+                                                }
                                             }
                                         }
-                                        self.bulk_array = Some(arg_array);
+                                        self.bulk_array = Some(self.dedup_bulk(arg_array, labels, lines));
                                     } else {
-                                        return Err(types::ParseError::throw_invalid("The file \"{filename}\" either doesnt exist, or doesn't have proper permissions"));
+                                        return Err(types::ParseError::throw_invalid(&format!("The file \"{filename}\" either doesnt exist, or doesn't have proper permissions")));
                                     }
                                 } else {
                                     return Err(types::ParseError::throw_incomplete("Bulk execute action is missing the filename to execute commands from"));
@@ -333,16 +321,72 @@ impl Args {
                         return Err(types::ParseError::throw_incomplete("Action is missing for bulk download"));
                     }
                 },
-                "list" | "l" => self.method = Some(types::Method::List),
+                "list" | "l" => {
+                    let instance = self.resolve_instance(self.raw.get(2).map(|i| i.to_lowercase()))?;
+                    self.method = Some(types::Method::List(instance))
+                },
                 "version" | "v" | "-v" | "--version" => self.method = Some(types::Method::Version),
                 "cobalt-version" | "cv" | "c" => {
-                    if self.raw.get(2).is_some() {
-                        self.method = Some(types::Method::CobaltVersion(self.raw[2].clone()))
-                    } else {
-                        self.method = Some(types::Method::CobaltVersion(String::from("api.cobalt.tools")))
-                    }
+                    let instance = self.resolve_instance(self.raw.get(2).map(|i| i.to_lowercase()))?;
+                    self.method = Some(types::Method::CobaltVersion(instance))
                 },
                 "gen-config" | "gc" => self.method = Some(types::Method::GenConfig),
+                "man" => self.method = Some(types::Method::Man),
+                "config" => {
+                    let action = match self.raw.get(2).map(|a| a.to_lowercase()) {
+                        Some(action) => action,
+                        None => return Err(types::ParseError::throw_incomplete("Action is missing for config"))
+                    };
+                    let Some(flags) = spec::CONFIG_ACTIONS.iter().find(|a| a.name == action).map(|a| a.flags) else {
+                        return Err(types::ParseError::throw_invalid(&format!("Invalid action: {action}")))
+                    };
+                    let args = self.raw.get(3..).unwrap_or_default().to_vec();
+                    let positionals = self.apply_flags(flags, &args)?;
+                    let position = |arg: &str| arg.parse::<usize>().ok().filter(|p| *p > 0)
+                        .ok_or_else(|| types::ParseError::throw_invalid(&format!("Invalid position: {arg} (the first instance is 1)")));
+                    let action = match (action.as_str(), positionals.as_slice()) {
+                        ("check", []) => types::ConfigAction::Check,
+                        ("list", []) => types::ConfigAction::List,
+                        ("get", [key]) => types::ConfigAction::Get(key.to_lowercase()),
+                        ("set", [key, value]) => types::ConfigAction::Set(key.to_lowercase(), value.clone()),
+                        ("unset", [key]) => types::ConfigAction::Unset(key.to_lowercase()),
+                        ("add-instance", [instance]) => types::ConfigAction::AddInstance(instance.clone(), None),
+                        ("add-instance", [instance, at]) => types::ConfigAction::AddInstance(instance.clone(), Some(position(at)?)),
+                        ("remove-instance", [instance]) => types::ConfigAction::RemoveInstance(instance.clone()),
+                        ("move-instance", [instance, to]) => types::ConfigAction::MoveInstance(instance.clone(), position(to)?),
+                        (action, args) => {
+                            let most = match action {
+                                "check" | "list" => 0,
+                                "get" | "unset" | "remove-instance" => 1,
+                                _ => 2
+                            };
+                            return Err(match args.get(most) {
+                                Some(arg) => types::ParseError::throw_invalid(&format!("Unrecognized argument: {arg}")),
+                                None => types::ParseError::throw_incomplete(&format!("Missing arguments for config {action}, see \"tcb help config\""))
+                            });
+                        }
+                    };
+                    self.method = Some(types::Method::Config(action));
+                },
+                "completions" => {
+                    let shell = match self.raw.get(2).map(|s| s.to_lowercase()) {
+                        Some(shell) => shell,
+                        None => return Err(types::ParseError::throw_incomplete(&format!("Missing the shell to generate completions for ({})", spec::SHELLS.join("/"))))
+                    };
+                    // "instances" is what the scripts call to complete instance numbers
+                    if !spec::SHELLS.contains(&shell.as_str()) && shell != "instances" {
+                        return Err(types::ParseError::throw_invalid(&format!("Unsupported shell: {shell} ({})", spec::SHELLS.join("/"))));
+                    }
+                    self.method = Some(types::Method::Completions(shell));
+                },
+                "instances" | "i" => {
+                    self.method = Some(types::Method::Instances);
+                    config::load_config_into(&mut Vec::new(), &mut self.instance_list, None).map_err(|e| types::ParseError::throw_config(&e))?;
+                    let args = self.raw[2..].to_vec();
+                    if let Some(arg) = self.apply_flags(&[spec::INSTANCES_FLAGS], &args)?.first() {
+                        return Err(types::ParseError::throw_invalid(&format!("Unrecognized argument: {arg}")));
+                    }
+                },
 
                 unknown => return Err(types::ParseError::throw_invalid(&format!("Unrecognized tcobalt method: {}", unknown)))
             },
@@ -351,6 +395,65 @@ impl Args {
         Ok(self)
     }
 
+    /// Drops entries of a bulk list that would request the exact same download, comparing their canonical urls,
+    /// and adds a warning that names the skipped entry and the one it duplicates.
+    /// The links themselves are sent to cobalt as they were given, since the canonical form can lose what cobalt needs (like music.youtube.com).
+    /// The source lines of the entries that are kept are stored in `bulk_lines`, for writing a retry file,
+    /// and the links that are dropped in `bulk_duplicates` along with the entry they duplicate, for the summary
+    fn dedup_bulk(&mut self, array: Vec<Self>, labels: Vec<String>, lines: Vec<String>) -> Vec<Self> {
+        let mut kept: Vec<Self> = Vec::new();
+        let mut seen: Vec<(String, String)> = Vec::new();
+        for ((args, label), line) in array.into_iter().zip(labels).zip(lines) {
+            let mut canonical = args.clone();
+            canonical.c_url = Some(crate::service::canonicalize(args.c_url.as_ref().unwrap()));
+            let key = format!("{}\n{}", args.cobalt_instance, crate::process::cobalt_args(&canonical));
+            if let Some((_, first)) = seen.iter().find(|(k, _)| k == &key) {
+                self.warnings.push(format!("Skipping {label}: it downloads the same media as {first}"));
+                self.bulk_duplicates.push((args.c_url.clone().unwrap_or_default(), format!("duplicate of {first}")));
+                continue;
+            }
+            seen.push((key, label));
+            self.bulk_lines.push(line);
+            kept.push(args);
+        }
+        kept
+    }
+
+    /// Sets every option in `args` from the given tables, returning the arguments that aren't options
+    fn apply_flags(&mut self, tables: &[&'static [spec::Flag]], args: &[String]) -> Result<Vec<String>, types::ParseError> {
+        let mut positionals: Vec<String> = Vec::new();
+        for (_, parsed) in spec::parse(tables, args)? {
+            match parsed {
+                spec::Parsed::Flag(flag, value) => {
+                    let value = flag.check(&value)?;
+                    (flag.apply)(self, &value).map_err(|e| types::ParseError::throw_invalid(&e))?;
+                },
+                spec::Parsed::Positional(arg) => positionals.push(arg)
+            }
+        }
+        Ok(positionals)
+    }
+
+    /// Turns an instance url or [default.instances] index into a bare host,
+    /// falling back to the config default and then the main instance
+    fn resolve_instance(&mut self, arg: Option<String>) -> Result<String, types::ParseError> {
+        config::load_config_into(&mut Vec::new(), &mut self.instance_list, None).map_err(|e| types::ParseError::throw_config(&e))?;
+        let default = config::get_default("instance", config::env_profile().as_deref()).map_err(|e| types::ParseError::throw_config(&e))?;
+        let arg = match arg.or(default) {
+            Some(arg) => arg,
+            None => return Ok(String::from("api.cobalt.tools"))
+        };
+        let url = if let Ok(choice) = arg.parse::<usize>() {
+            match choice.checked_sub(1).and_then(|i| self.instance_list.get(i)) {
+                Some(url) => url.clone(),
+                None => return Err(types::ParseError::throw_invalid("Invalid instance quick-choice"))
+            }
+        } else {
+            arg
+        };
+        Ok(config::host(&url))
+    }
+
     pub fn override_args(args: &[&str]) -> Self {
         let mut args = args.to_vec().iter().map(|str| str.to_string()).collect::<Vec<String>>();
         args.insert(0, "tcb".to_string());
@@ -359,8 +462,3 @@ impl Args {
         template
     }
 }
-
-#[derive(Debug)]
-enum ExpectedFlags {
-    VideoCodec, VideoQuality, AudioFormat, Output, FilenamePattern, Picker, Language, Instance, Bitrate
-}
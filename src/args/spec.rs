@@ -0,0 +1,579 @@
+//! The single description of every method and option, which parsing, config loading, help, completions and the man page all work from.
+//! Adding an option means adding one `Flag` to a table here
+
+use super::{config, types, Args};
+
+/// What kind of value an option takes, which decides how it is validated and completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    /// The option is a switch and takes no value
+    Switch,
+    /// One of a fixed set of values
+    Choice(&'static [&'static str]),
+    File,
+    Dir,
+    /// An instance url or a number from [default.instances]
+    Instance,
+    /// Free text, like a language tag or a command
+    Text
+}
+
+/// How an option is set from the [default] section of the config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Config {
+    /// The option can't be set in the config file
+    None,
+    /// `key = value`, or `key = true` for a switch. Options that take a value are left unset with `key = none`
+    Key(&'static str),
+    /// The switch is turned on by `key = value`, for keys that choose between several switches
+    When(&'static str, &'static str)
+}
+
+/// A single command-line option
+#[derive(Debug, Clone, Copy)]
+pub struct Flag {
+    pub long: &'static str,
+    pub short: Option<char>,
+    pub value: Value,
+    /// What the value is called in help, like <quality>
+    pub meta: &'static str,
+    /// The value used when the option isn't given, or "true" for the switch that is on by default
+    pub default: Option<&'static str>,
+    pub config: Config,
+    /// A one line description, used for completions and as the first line in help
+    pub help: &'static str,
+    /// Lines that only the full help and the man page show
+    pub details: &'static [&'static str],
+    /// Sets the option on the parsed args. Values of a `Choice` are checked before this is called
+    pub apply: fn(&mut Args, &str) -> Result<(), String>
+}
+impl Flag {
+    const fn switch(long: &'static str, short: Option<char>, help: &'static str, apply: fn(&mut Args, &str) -> Result<(), String>) -> Self {
+        Self { long, short, value: Value::Switch, meta: "", default: None, config: Config::None, help, details: &[], apply }
+    }
+    const fn takes(long: &'static str, short: Option<char>, meta: &'static str, value: Value, help: &'static str, apply: fn(&mut Args, &str) -> Result<(), String>) -> Self {
+        Self { long, short, value, meta, default: None, config: Config::None, help, details: &[], apply }
+    }
+    const fn default(self, default: &'static str) -> Self {
+        Self { default: Some(default), ..self }
+    }
+    const fn config(self, config: Config) -> Self {
+        Self { config, ..self }
+    }
+    const fn details(self, details: &'static [&'static str]) -> Self {
+        Self { details, ..self }
+    }
+
+    /// Checks a value given for the option, turning an abbreviated choice into the full one.
+    /// A choice can be shortened to its first letter when no other choice starts with it
+    pub fn check(&self, value: &str) -> Result<String, types::ParseError> {
+        let choices = match self.value {
+            Value::Choice(choices) => choices,
+            _ => return Ok(value.to_string())
+        };
+        let value = value.to_lowercase();
+        if choices.contains(&value.as_str()) {
+            return Ok(value);
+        }
+        let letter = value.len() == 1 && value.chars().all(|c| c.is_ascii_alphabetic());
+        let abbreviated: Vec<&&str> = choices.iter().filter(|c| letter && c.starts_with(&value)).collect();
+        match abbreviated.as_slice() {
+            [choice] => Ok(choice.to_string()),
+            _ => Err(types::ParseError::throw_invalid(&format!("Invalid value for --{}: {value} ({})", self.long, choices.join("/"))))
+        }
+    }
+
+    /// The command-line arguments that a `key = value` line from [default] turns into for this option
+    pub fn config_args(&self, key: &str, value: &str) -> Vec<String> {
+        match self.config {
+            Config::Key(name) if name == key => match self.value {
+                Value::Switch if value.eq_ignore_ascii_case("true") => vec![format!("--{}", self.long)],
+                Value::Switch => Vec::new(),
+                _ if value.is_empty() || value.eq_ignore_ascii_case("none") => Vec::new(),
+                _ => vec![format!("--{}", self.long), value.to_string()]
+            },
+            Config::When(name, when) if name == key && value.eq_ignore_ascii_case(when) => vec![format!("--{}", self.long)],
+            _ => Vec::new()
+        }
+    }
+
+    /// The names column of help, like "-q --vquality <quality>"
+    fn names(&self) -> String {
+        let short = self.short.map(|s| format!("-{s}")).unwrap_or(String::from("  "));
+        match self.value {
+            Value::Switch => format!("{short} --{}", self.long),
+            _ => format!("{short} --{} <{}>", self.long, self.meta)
+        }
+    }
+}
+
+/// What a method takes besides its options
+#[derive(Debug, Clone, Copy)]
+pub enum Positional {
+    None,
+    Url,
+    Instance,
+    File,
+    Choice(&'static [&'static str]),
+    /// A key of the [default] section, as `config_defaults` lists them
+    ConfigKey,
+    /// The first argument is an action, which has options of its own
+    Actions(&'static [Method])
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Method {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub positional: Positional,
+    pub flags: &'static [&'static [Flag]],
+    pub help: &'static str
+}
+
+pub const QUALITIES: &[&str] = &["144", "480", "720", "1080", "1440", "2160"];
+pub const CODECS: &[&str] = &["h264", "av1", "vp9"];
+pub const AUDIO_FORMATS: &[&str] = &["best", "mp3", "ogg", "wav", "opus"];
+pub const BITRATES: &[&str] = &["320", "256", "128", "96", "64", "8"];
+pub const FILENAME_STYLES: &[&str] = &["classic", "pretty", "basic", "nerdy"];
+pub const COLLISIONS: &[&str] = &["overwrite", "skip", "rename", "fail"];
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+pub const HELP_TOPICS: &[&str] = &["get", "url", "info", "list", "bulk", "help", "examples", "config", "gen-config", "instances", "completions", "man", "environment"];
+
+const DRY_RUN: Flag = Flag::switch("dry-run", None, "Shows what would be done without contacting cobalt or downloading anything", |args, _| {
+    args.dry_run = true;
+    Ok(())
+});
+const RETRY_FILE: Flag = Flag::takes("retry-file", None, "file", Value::File, "Writes the links that failed to this file, to retry them with \"bulk execute\"", |args, path| {
+    args.retry_file = Some(path.to_string());
+    Ok(())
+});
+
+/// The options of "get" that apply to every service, which "url", "info" and "bulk get" take as well
+pub const GET_FLAGS: &[Flag] = &[
+    Flag::takes("vquality", Some('q'), "quality", Value::Choice(QUALITIES), "The quality of the output video", |args, quality| {
+        args.c_video_quality = quality.parse().unwrap();
+        Ok(())
+    }).default("1080").config(Config::Key("vquality")),
+    Flag::takes("aformat", Some('f'), "format", Value::Choice(AUDIO_FORMATS), "The format of the audio", |args, format| {
+        args.c_audio_format = match format {
+            "best" => types::AudioFormat::BEST,
+            "ogg" => types::AudioFormat::OGG,
+            "wav" => types::AudioFormat::WAV,
+            "opus" => types::AudioFormat::OPUS,
+            _ => types::AudioFormat::MP3
+        };
+        Ok(())
+    }).default("mp3").config(Config::Key("aformat"))
+        .details(&["The \"best\" option takes the format of the original audio, and may not be specified on the website you took the media from."]),
+    Flag::takes("bitrate", Some('b'), "bitrate", Value::Choice(BITRATES), "The bitrate of the audio if it was reformatted", |args, bitrate| {
+        args.c_audio_bitrate = bitrate.parse().unwrap();
+        Ok(())
+    }).default("128").config(Config::Key("bitrate")),
+    Flag::switch("audio-only", Some('a'), "Tells cobalt to only download and output the audio of the link", |args, _| {
+        args.c_download_mode = types::DownloadMode::Audio;
+        Ok(())
+    }).config(Config::When("download-mode", "audio")),
+    Flag::switch("mute-audio", Some('m'), "Tells cobalt to mute the audio from the downloaded content", |args, _| {
+        args.c_download_mode = types::DownloadMode::Mute;
+        Ok(())
+    }).config(Config::When("download-mode", "mute")),
+    Flag::switch("auto", Some('='), "Tells cobalt to download a video if possible, otherwise downloading audio instead", |args, _| {
+        args.c_download_mode = types::DownloadMode::Auto;
+        Ok(())
+    }).default("true").config(Config::When("download-mode", "auto"))
+        .details(&["This is the default option, only use this if your config default is something else."]),
+    Flag::switch("no-metadata", Some('n'), "Prevents the downloaded media from including metadata", |args, _| {
+        args.c_disable_metadata = !args.c_disable_metadata;
+        Ok(())
+    }).config(Config::Key("no-metadata")),
+    Flag::takes("fname-style", Some('s'), "style", Value::Choice(FILENAME_STYLES), "Tells cobalt which filename style to return", |args, style| {
+        args.c_fname_style = match style {
+            "pretty" => types::FilenamePattern::Pretty,
+            "basic" => types::FilenamePattern::Basic,
+            "nerdy" => types::FilenamePattern::Nerdy,
+            _ => types::FilenamePattern::Classic
+        };
+        Ok(())
+    }).default("classic").config(Config::Key("fname-style"))
+        .details(&["This option will not matter if -o or --output is specified"]),
+    Flag::takes("output", Some('o'), "filename", Value::File, "The output filename, a template for it, or - for stdout", |args, output| {
+        if output == "-" {
+            args.stdout = true;
+        } else if output.contains('{') {
+            let fields: Vec<(&str, String)> = crate::process::TEMPLATE_FIELDS.iter().map(|f| (*f, String::new())).collect();
+            crate::process::render_template(output, &fields)?;
+            args.out_filename = Some(output.to_string());
+        } else if [".mp3", ".ogg", ".wav", ".opus", ".mp4", ".webm", ".gif"].iter().any(|ext| output.to_lowercase().contains(ext)) {
+            args.out_filename = Some(output.to_string());
+        } else {
+            return Err(String::from("Output filename must be a video file type (supported: mp4/webm/gif), or an audio file type (supported: mp3/ogg/wav/opus)\nMake sure you choose the right file type for the chosen codec/format!"));
+        }
+        Ok(())
+    }).details(&[
+        "Make sure to include the proper file extension",
+        "If no filename is specified, it uses the internal filename of the video, or the hash of the url if none is available",
+        "The filename can also be a template like '{service}/{title}.{ext}', with folders created as needed. Fields:",
+        "{filename} {title} {ext} from cobalt's filename, {service} {id} from the link, {index} in bulk, {type} audio/video",
+        "Use '-o -' to write the media to stdout instead, for piping into a player or encoder. Status messages go to stderr",
+        "This can't be used with bulk downloads, and needs -p when cobalt returns more than one item to pick from"
+    ]),
+    Flag::takes("output-dir", Some('d'), "dir", Value::Dir, "Writes the downloaded media into this directory instead of the current one, creating it if needed", |args, dir| {
+        args.output_dir = Some(dir.to_string());
+        Ok(())
+    }).config(Config::Key("output-dir")),
+    Flag::takes("if-exists", None, "policy", Value::Choice(COLLISIONS), "What to do when the output file already exists", |args, policy| {
+        args.if_exists = match policy {
+            "overwrite" => types::Collision::Overwrite,
+            "skip" => types::Collision::Skip,
+            "fail" => types::Collision::Fail,
+            _ => types::Collision::Rename
+        };
+        Ok(())
+    }).default("rename").config(Config::Key("if-exists")).details(&[
+        "rename adds a number to the filename, like 'video (1).mp4'",
+        "Filenames are always cleaned of path separators and characters that aren't allowed on Windows or Linux"
+    ]),
+    Flag::takes("pick", Some('p'), "number", Value::Text, "Downloads the nth item when cobalt returns several (like the photos of a post) instead of asking", |args, choice| {
+        args.picker_choice = choice.parse().map_err(|_| String::from("Picker choice must be an integer between 0 and 255"))?;
+        Ok(())
+    }),
+    Flag::takes("instance", Some('i'), "url | idx", Value::Instance, "The cobalt instance to send the requests to", |args, instance| {
+        let instance = instance.to_lowercase();
+        let url = match instance.parse::<usize>() {
+            Ok(choice) => match choice.checked_sub(1).and_then(|i| args.instance_list.get(i)) {
+                Some(url) => url.clone(),
+                None => return Err(String::from("Invalid instance quick-choice"))
+            },
+            Err(_) => instance
+        };
+        args.cobalt_instance = config::host(&url);
+        Ok(())
+    }).default("api.cobalt.tools").config(Config::Key("instance"))
+        .details(&["You can specify a number n instead, and it will take the nth line in the [default.instances] section of the config file"]),
+    // the profile is picked out by `Args::parse` before the config file is loaded, since it decides what gets loaded
+    Flag::takes("profile", None, "name", Value::Text, "Uses the defaults of the [profile.NAME] section of the config file on top of [default]", |args, profile| {
+        args.profile = Some(profile.to_string());
+        Ok(())
+    })
+        .details(&["Overrides the TCOBALT_PROFILE environment variable"]),
+    Flag::switch("proxy", Some('x'), "Tells cobalt to tunnel the media through itself instead of sending a link straight to the service", |args, _| {
+        args.c_proxy = !args.c_proxy;
+        Ok(())
+    }).config(Config::Key("proxy")),
+    Flag::takes("archive", None, "file", Value::File, "Keeps a list of everything downloaded in the given file, and skips links that are already in it", |args, path| {
+        args.archive = Some(path.to_string());
+        Ok(())
+    }).config(Config::Key("archive"))
+        .details(&["Each link is recorded with the options it was downloaded with, so the same link with other options is still downloaded"]),
+    Flag::switch("force", None, "Downloads the link even if it is already in the archive file", |args, _| {
+        args.force = !args.force;
+        Ok(())
+    }),
+    Flag::takes("exec", None, "command", Value::Text, "Runs the command through the shell after each file is written, like --exec 'ffmpeg -i {path} {path}.mkv'", |args, command| {
+        let fields: Vec<(&str, String)> = crate::process::EXEC_FIELDS.iter().map(|f| (*f, String::new())).collect();
+        crate::process::render_template(command, &fields)?;
+        args.exec = Some(command.to_string());
+        Ok(())
+    }).config(Config::Key("exec")).details(&[
+        "Fields: {path} of the written file, {url} it was downloaded from, {service}, and {type} audio/video",
+        "Fields are quoted for the shell already, so don't put quotes around them. If the command fails, tcobalt exits with an error, but the download is kept and archived so a retry doesn't download it again"
+    ]),
+    Flag::switch("failover", None, "If the instance is down or returns an instance-specific error, tries the next instances in the [default.instances] section", |args, _| {
+        args.failover = !args.failover;
+        Ok(())
+    }).config(Config::Key("failover"))
+        .details(&["Every skipped instance is printed along with the reason it was skipped"])
+];
+/// The options of "get" that only apply to some services, which tcobalt warns about for links from other services
+pub const SERVICE_FLAGS: &[Flag] = &[
+    Flag::takes("vcodec", Some('c'), "codec", Value::Choice(CODECS), "The codec of the output [YOUTUBE] video", |args, codec| {
+        args.c_video_codec = match codec {
+            "av1" => types::VideoCodec::AV1,
+            "vp9" => types::VideoCodec::VP9,
+            _ => types::VideoCodec::H264
+        };
+        Ok(())
+    }).default("h264").config(Config::Key("vcodec"))
+        .details(&["h264 and av1 are for .mp4 files, vp9 is for .webm files."]),
+    Flag::takes("dublang", Some('l'), "lang", Value::Text, "Tells cobalt to attempt downloading the [YOUTUBE] video with audio tracks in the given IETF language tag based off ISO 639-1", |args, lang| {
+        args.accept_language = lang.to_lowercase();
+        Ok(())
+    }).default("en").config(Config::Key("dublang")).details(&[
+        "IETF language tags have a specific format. Some examples are: en, es, ja, en-US, fr-CA, and zh-Hans",
+        "More about IETF language tags here: https://www.w3.org/International/articles/language-tags/"
+    ]),
+    Flag::switch("twitter-gif", Some('g'), "Tells cobalt to download the given [TWITTER] content as a gif", |args, _| {
+        args.c_twitter_gif = !args.c_twitter_gif;
+        Ok(())
+    }).config(Config::Key("twitter-gif")),
+    Flag::switch("tt-full-audio", Some('u'), "Tells cobalt to download the [TIKTOK] audio without modifications from its post author", |args, _| {
+        args.c_tt_full_audio = !args.c_tt_full_audio;
+        Ok(())
+    }).config(Config::Key("tt-full-audio")),
+    Flag::switch("tt-h265", Some('h'), "Suggests cobalt to try downloading the [TIKTOK] video with the h265 codec", |args, _| {
+        args.c_tt_h265 = !args.c_tt_h265;
+        Ok(())
+    }).config(Config::Key("tt-h265"))
+];
+/// The options of "get" that "url" and "info" don't take, since those never download anything
+pub const DOWNLOAD_FLAGS: &[Flag] = &[
+    DRY_RUN.details(&[
+        "Prints the detected service, instance, output path and the request for cobalt",
+        "Exits with an error if the link would be rejected, so job files can be checked ahead of time"
+    ])
+];
+pub const URL_FLAGS: &[Flag] = &[
+    Flag::switch("json", None, "Prints the links, the filename, the detected service and the instance as JSON", |args, _| {
+        args.json_output = true;
+        Ok(())
+    })
+];
+pub const BULK_GET_FLAGS: &[Flag] = &[
+    // the files are read by the "bulk get" action itself, since the links in them aren't options of "get"
+    Flag::takes("from-file", None, "file", Value::File, "Reads links from a file, one per line", |_, _| Ok(())),
+    RETRY_FILE
+];
+pub const BULK_EXECUTE_FLAGS: &[Flag] = &[
+    DRY_RUN.details(&["Every line is checked and its plan is printed"]),
+    RETRY_FILE
+];
+pub const INSTANCES_FLAGS: &[Flag] = &[
+    Flag::switch("json", Some('j'), "Prints the results as JSON instead of a table", |args, _| {
+        args.json_output = true;
+        Ok(())
+    }),
+    Flag::switch("reorder", Some('r'), "Sorts the instances by health (working instances first, fastest to slowest)", |args, _| {
+        args.reorder_instances = true;
+        Ok(())
+    }).details(&[
+        "and rewrites the [default.instances] section of the config file in that order",
+        "Keep in mind this changes which instance the numbers in `-i <idx>` refer to"
+    ])
+];
+
+pub const BULK_ACTIONS: &[Method] = &[
+    Method { name: "get", aliases: &["g"], positional: Positional::Url, flags: &[GET_FLAGS, SERVICE_FLAGS, DOWNLOAD_FLAGS, BULK_GET_FLAGS], help: "Download a list of links with the same options" },
+    Method { name: "execute", aliases: &["exe", "e"], positional: Positional::File, flags: &[BULK_EXECUTE_FLAGS], help: "Run get commands from a file, one per line" }
+];
+
+pub const CONFIG_FLAGS: &[Flag] = &[
+    Flag::takes("profile", None, "name", Value::Text, "Reads or changes the [profile.NAME] section instead of [default]", |args, profile| {
+        args.profile = Some(profile.to_string());
+        Ok(())
+    })
+];
+
+pub const CONFIG_ACTIONS: &[Method] = &[
+    Method { name: "check", aliases: &[], positional: Positional::None, flags: &[], help: "Report every mistake in the config file" },
+    Method { name: "list", aliases: &[], positional: Positional::None, flags: &[CONFIG_FLAGS], help: "Show the value of every key and where it comes from" },
+    Method { name: "get", aliases: &[], positional: Positional::ConfigKey, flags: &[CONFIG_FLAGS], help: "Show the value of a key and where it comes from" },
+    Method { name: "set", aliases: &[], positional: Positional::ConfigKey, flags: &[CONFIG_FLAGS], help: "Set a key, leaving the rest of the file as it is" },
+    Method { name: "unset", aliases: &[], positional: Positional::ConfigKey, flags: &[CONFIG_FLAGS], help: "Remove a key, so its default is used again" },
+    Method { name: "add-instance", aliases: &[], positional: Positional::Url, flags: &[], help: "Add an instance to [default.instances]" },
+    Method { name: "remove-instance", aliases: &[], positional: Positional::Instance, flags: &[], help: "Remove an instance from [default.instances]" },
+    Method { name: "move-instance", aliases: &[], positional: Positional::Instance, flags: &[], help: "Move an instance to another number in [default.instances]" }
+];
+
+/// Every method, in the order they are listed in help
+pub const METHODS: &[Method] = &[
+    Method { name: "get", aliases: &["g"], positional: Positional::Url, flags: &[GET_FLAGS, SERVICE_FLAGS, DOWNLOAD_FLAGS], help: "Download media from a link" },
+    Method { name: "url", aliases: &["u"], positional: Positional::Url, flags: &[GET_FLAGS, SERVICE_FLAGS, URL_FLAGS], help: "Print the link to download media from" },
+    Method { name: "info", aliases: &[], positional: Positional::Url, flags: &[GET_FLAGS, SERVICE_FLAGS], help: "Show what cobalt returns for a link" },
+    Method { name: "list", aliases: &["l"], positional: Positional::Instance, flags: &[], help: "List the services an instance supports" },
+    Method { name: "help", aliases: &["h"], positional: Positional::Choice(HELP_TOPICS), flags: &[], help: "Show help for a method" },
+    Method { name: "bulk", aliases: &["b"], positional: Positional::Actions(BULK_ACTIONS), flags: &[], help: "Download many links at once" },
+    Method { name: "gen-config", aliases: &["gc"], positional: Positional::None, flags: &[], help: "Write the default config file" },
+    Method { name: "config", aliases: &[], positional: Positional::Actions(CONFIG_ACTIONS), flags: &[], help: "Check or change the config file" },
+    Method { name: "version", aliases: &["v"], positional: Positional::None, flags: &[], help: "Show the tcobalt version" },
+    Method { name: "cobalt-version", aliases: &["cv", "c"], positional: Positional::Instance, flags: &[], help: "Show an instance's cobalt version" },
+    Method { name: "instances", aliases: &["i"], positional: Positional::None, flags: &[INSTANCES_FLAGS], help: "Check the health of the configured instances" },
+    Method { name: "completions", aliases: &[], positional: Positional::Choice(SHELLS), flags: &[], help: "Print a shell completion script" },
+    Method { name: "man", aliases: &[], positional: Positional::None, flags: &[], help: "Print the man page" }
+];
+
+impl Method {
+    /// The name and aliases together
+    pub fn names(&self) -> Vec<&'static str> {
+        std::iter::once(self.name).chain(self.aliases.iter().copied()).collect()
+    }
+    pub fn all_flags(&self) -> impl Iterator<Item = &'static Flag> {
+        self.flags.iter().flat_map(|flags| flags.iter())
+    }
+}
+
+/// The options that `key = value` lines in [default] are matched against
+pub fn config_flags() -> impl Iterator<Item = &'static Flag> {
+    GET_FLAGS.iter().chain(SERVICE_FLAGS)
+}
+
+/// A command-line argument, sorted out by `parse`
+#[derive(Debug, Clone)]
+pub enum Parsed {
+    /// An option and its value, which is empty for switches
+    Flag(&'static Flag, String),
+    /// Anything that isn't an option or the value of one
+    Positional(String)
+}
+
+/// Sorts arguments into options with their values and everything else, using the given option tables.
+/// Long options are matched case-insensitively, and can be shortened to any prefix that only one option starts with.
+/// Their value can follow as the next argument or be attached like --vquality=720.
+/// Short options can be grouped like -mqo, with their values following in the same order, or have their value attached like -q720
+/// when the rest of the argument isn't made of other short options (or is one of the option's choices).
+/// Everything after a lone -- is left as it is.
+/// Each result comes with the index of the argument it started at
+pub fn parse(tables: &[&'static [Flag]], args: &[String]) -> Result<Vec<(usize, Parsed)>, types::ParseError> {
+    let flags = || tables.iter().flat_map(|t| t.iter());
+    let mut parsed: Vec<(usize, Parsed)> = Vec::new();
+    let mut expected: Vec<(usize, &'static Flag)> = Vec::new();
+    let mut options_ended = false;
+
+    for (idx, original) in args.iter().enumerate() {
+        if !expected.is_empty() {
+            let (at, flag) = expected.remove(0);
+            parsed.push((at, Parsed::Flag(flag, original.clone())));
+            continue;
+        }
+        let arg = original.to_lowercase();
+        if options_ended {
+            parsed.push((idx, Parsed::Positional(original.clone())));
+        } else if arg == "--" {
+            options_ended = true;
+        } else if let Some(long) = arg.strip_prefix("--") {
+            let (name, attached) = match long.split_once('=') {
+                Some((name, _)) => (name, original.split_once('=').map(|(_, value)| value.to_string())),
+                None => (long, None)
+            };
+            let flag = match flags().find(|f| f.long == name) {
+                Some(flag) => flag,
+                None => {
+                    let candidates: Vec<&Flag> = flags().filter(|f| f.long.starts_with(name)).collect();
+                    match candidates.as_slice() {
+                        [flag] => *flag,
+                        [] => return Err(types::ParseError::throw_invalid(&format!("Unrecognized argument: {original}"))),
+                        _ => {
+                            let names: Vec<String> = candidates.iter().map(|f| format!("--{}", f.long)).collect();
+                            return Err(types::ParseError::throw_invalid(&format!("Ambiguous argument ({}): {original}", names.join(", "))));
+                        }
+                    }
+                }
+            };
+            match (flag.value, attached) {
+                (Value::Switch, Some(_)) => return Err(types::ParseError::throw_invalid(&format!("--{} doesn't take a value: {original}", flag.long))),
+                (Value::Switch, None) => parsed.push((idx, Parsed::Flag(flag, String::new()))),
+                (_, Some(value)) => parsed.push((idx, Parsed::Flag(flag, value))),
+                (_, None) => expected.push((idx, flag))
+            }
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            let chars: Vec<char> = arg.chars().collect();
+            for (i, c) in chars.iter().enumerate().skip(1) {
+                let flag = match flags().find(|f| f.short == Some(*c)) {
+                    Some(flag) => flag,
+                    None => return Err(types::ParseError::throw_invalid(&format!("Invalid character {c} in multi-flag argument: {arg}")))
+                };
+                if flag.value == Value::Switch {
+                    parsed.push((idx, Parsed::Flag(flag, String::new())));
+                    continue;
+                }
+                let rest: String = original.chars().skip(i + 1).collect();
+                let grouped = chars[i + 1..].iter().all(|c| flags().any(|f| f.short == Some(*c)));
+                let choice = matches!(flag.value, Value::Choice(choices) if choices.contains(&rest.to_lowercase().as_str()));
+                if !rest.is_empty() && (choice || !grouped) {
+                    parsed.push((idx, Parsed::Flag(flag, rest.strip_prefix('=').unwrap_or(&rest).to_string())));
+                    break;
+                }
+                expected.push((idx, flag));
+            }
+        } else {
+            parsed.push((idx, Parsed::Positional(original.clone())));
+        }
+    }
+    if !expected.is_empty() {
+        let missing: Vec<String> = expected.iter().map(|(_, f)| format!("--{}", f.long)).collect();
+        return Err(types::ParseError::throw_incomplete(&format!("The following flags were specified but their values were not: {}", missing.join(", "))));
+    }
+    Ok(parsed)
+}
+
+/// Lists options the way "tcb help" does: the names, the description with the choices and the default, then the details on lines of their own
+pub fn options_help(flags: &[Flag]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for flag in flags {
+        let mut description = flag.help.to_string();
+        match (flag.value, flag.default) {
+            (Value::Choice(choices), Some(default)) => description.push_str(&format!(". Options: {}, Default: {default}", choices.join(", "))),
+            (Value::Choice(choices), None) => description.push_str(&format!(". Options: {}", choices.join(", "))),
+            (Value::Switch, _) | (_, None) => (),
+            (_, Some(default)) => description.push_str(&format!(". Default: {default}"))
+        }
+        lines.push(format!("\t{:<25}  {description}", flag.names()));
+        for detail in flag.details {
+            lines.push(format!("\t\t\t\t   {detail}"));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Lists every key of the [default] section with the values it takes
+pub fn config_help() -> String {
+    let mut entries: Vec<(String, String)> = Vec::new();
+    for flag in config_flags() {
+        match flag.config {
+            Config::None => (),
+            Config::Key(key) => {
+                let values = match flag.value {
+                    Value::Switch => String::from("true/false"),
+                    Value::Choice(choices) => choices.join("/"),
+                    _ => format!("<{}>/none", flag.meta)
+                };
+                entries.push((format!("{key} = {values}"), format!("{} (see --{})", flag.help, flag.long)));
+            },
+            Config::When(key, _) => {
+                if entries.iter().any(|(entry, _)| entry.starts_with(&format!("{key} ="))) {
+                    continue;
+                }
+                let group: Vec<&Flag> = config_flags().filter(|f| matches!(f.config, Config::When(k, _) if k == key)).collect();
+                let values: Vec<&str> = group.iter().filter_map(|f| match f.config { Config::When(_, v) => Some(v), _ => None }).collect();
+                let names: Vec<String> = group.iter().map(|f| format!("--{}", f.long)).collect();
+                entries.push((format!("{key} = {}", values.join("/")), format!("Same as {}", names.join(" / "))));
+            }
+        }
+    }
+    let width = entries.iter().map(|(entry, _)| entry.len()).max().unwrap_or(0);
+    entries.iter().map(|(entry, description)| format!("\t{entry:<width$}   {description}")).collect::<Vec<String>>().join("\n")
+}
+
+/// The [default] section that "tcb gen-config" writes, with every key set to its default
+pub fn default_config() -> String {
+    let mut config = String::from("[default]\n");
+    for (key, value) in config_defaults() {
+        config.push_str(&format!("{key} = {value}\n"));
+    }
+    config
+}
+
+/// Every key of the [default] section with the value it has when the config file doesn't set it
+pub fn config_defaults() -> Vec<(&'static str, &'static str)> {
+    config_flags().filter_map(|flag| match flag.config {
+        Config::None => None,
+        Config::Key(key) => Some((key, match flag.value {
+            Value::Switch => "false",
+            _ => flag.default.unwrap_or("none")
+        })),
+        Config::When(key, value) if flag.default == Some("true") => Some((key, value)),
+        Config::When(_, _) => None
+    }).collect()
+}
+
+/// A section of usage.txt, with its {options <table>} and {config} lines filled in from the tables above
+pub fn usage(section: &str) -> String {
+    crate::strings::get_str("usage", section).lines().map(|line| match line.trim() {
+        "{options get}" => options_help(GET_FLAGS),
+        "{options download}" => options_help(DOWNLOAD_FLAGS),
+        "{options service}" => options_help(SERVICE_FLAGS),
+        "{options url}" => options_help(URL_FLAGS),
+        "{options instances}" => options_help(INSTANCES_FLAGS),
+        "{config}" => config_help(),
+        _ => line.to_string()
+    }).collect::<Vec<String>>().join("\n")
+}
@@ -52,20 +52,44 @@ impl Default for DownloadMode {
     }
 }
 
+/// What to do when the output file already exists
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub enum Collision {
+    Overwrite, Skip, #[default] Rename, Fail
+}
+impl Collision {
+    pub fn print(&self) -> String {
+        format!("{self:?}").to_lowercase()
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Help {
-    Get, List, Bulk, Help, Examples, Config, GenConfig
+    Get, List, Bulk, Help, Examples, Config, GenConfig, Instances, Url, Info, Completions, Man, Environment
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Method {
-    Get, List, Bulk, Help, Version, CobaltVersion(String), GenConfig
+    Get, List(String), Bulk, Help, Version, CobaltVersion(String), GenConfig, Instances, Url, Info, Completions(String), Man, Config(ConfigAction)
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ConfigAction {
+    Check,
+    List,
+    Get(String),
+    Set(String, String),
+    Unset(String),
+    /// An instance, and the number to give it
+    AddInstance(String, Option<usize>),
+    RemoveInstance(String),
+    MoveInstance(String, usize)
 }
 
 #[derive(Debug, PartialEq, Eq)]
 enum ParseErrType {
     InvalidArg,
     Incomplete,
-    BulkParseError
+    BulkParseError,
+    Config
 }
 #[derive(Debug)]
 pub struct ParseError {
@@ -91,6 +115,19 @@ impl ParseError {
             message: message.to_string()
         }
     }
+    pub fn throw_config(message: &str) -> Self {
+        Self {
+            err_type: ParseErrType::Config,
+            message: message.to_string()
+        }
+    }
+    /// The line printed above the error, since a broken config file isn't the command's fault
+    pub fn title(&self) -> &'static str {
+        match self.err_type {
+            ParseErrType::Config => "Invalid config file",
+            _ => "Invalid command syntax"
+        }
+    }
     pub fn print(&self) -> String {
         format!("{:?}: {}", self.err_type , &self.message)
     }
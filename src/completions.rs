@@ -0,0 +1,222 @@
+use crate::args::spec::{self, Flag, Method, Positional, Value};
+
+/// Prints the completion script for a shell, or the configured instances for the scripts to complete with
+pub fn print(shell: &str) {
+    match shell {
+        "bash" => print!("{}", bash()),
+        "zsh" => print!("{}", zsh()),
+        "fish" => print!("{}", fish()),
+        "instances" => {
+            let mut instances: Vec<String> = Vec::new();
+            crate::args::config::load_config_into(&mut Vec::new(), &mut instances, None).ok();
+            for (i, instance) in instances.iter().enumerate() {
+                println!("{}\t{instance}", i + 1);
+            }
+        },
+        _ => unreachable!()
+    }
+}
+
+fn flag_words(method: &Method) -> Vec<String> {
+    method.all_flags().flat_map(|flag| {
+        std::iter::once(format!("--{}", flag.long)).chain(flag.short.map(|s| format!("-{s}")))
+    }).collect()
+}
+
+/// The keys of the config file, taken from the options so a new key completes without being listed anywhere else
+fn config_keys() -> String {
+    spec::config_defaults().iter().map(|(key, _)| *key).collect::<Vec<&str>>().join(" ")
+}
+
+pub fn bash() -> String {
+    let mut script = String::from("# bash completion for tcb, generated by `tcb completions bash`\n\n");
+    script.push_str("_tcb_instances() {\n    \"${COMP_WORDS[0]}\" completions instances 2>/dev/null | cut -f1\n}\n\n");
+    script.push_str("_tcb() {\n    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    let names: Vec<&str> = spec::METHODS.iter().map(|m| m.name).collect();
+    script.push_str(&format!("    if [ \"$COMP_CWORD\" -eq 1 ]; then\n        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n        return\n    fi\n", names.join(" ")));
+    script.push_str("    case \"${COMP_WORDS[1]}\" in\n");
+    for method in spec::METHODS {
+        script.push_str(&format!("        {})\n", method.names().join("|")));
+        script.push_str(&bash_method(method, 2, "            "));
+        script.push_str("            ;;\n");
+    }
+    script.push_str("    esac\n}\n\ncomplete -F _tcb tcb tcobalt\n");
+    script
+}
+
+/// The body of a case branch that completes a method whose first argument is at word `position`
+fn bash_method(method: &Method, position: usize, indent: &str) -> String {
+    let mut body = String::new();
+    let reply = |words: &str| format!("COMPREPLY=($(compgen -W \"{words}\" -- \"$cur\"))");
+
+    let value_flags: Vec<&Flag> = method.all_flags().filter(|f| f.value != Value::Switch).collect();
+    if !value_flags.is_empty() {
+        body.push_str(&format!("{indent}case \"$prev\" in\n"));
+        for flag in value_flags {
+            let names = std::iter::once(format!("--{}", flag.long)).chain(flag.short.map(|s| format!("-{s}"))).collect::<Vec<String>>().join("|");
+            let complete = match flag.value {
+                Value::Choice(choices) => reply(&choices.join(" ")),
+                Value::File => String::from("COMPREPLY=($(compgen -f -- \"$cur\"))"),
+                Value::Dir => String::from("COMPREPLY=($(compgen -d -- \"$cur\"))"),
+                Value::Instance => reply("$(_tcb_instances)"),
+                Value::Text | Value::Switch => String::from("COMPREPLY=()")
+            };
+            body.push_str(&format!("{indent}    {names}) {complete}; return ;;\n"));
+        }
+        body.push_str(&format!("{indent}esac\n"));
+    }
+
+    let flags = flag_words(method);
+    if !flags.is_empty() {
+        body.push_str(&format!("{indent}if [[ \"$cur\" == -* ]]; then\n{indent}    {}\n{indent}    return\n{indent}fi\n", reply(&flags.join(" "))));
+    }
+    match method.positional {
+        Positional::None | Positional::Url => (),
+        Positional::File => body.push_str(&format!("{indent}COMPREPLY=($(compgen -f -- \"$cur\"))\n")),
+        Positional::Instance => body.push_str(&format!("{indent}[ \"$COMP_CWORD\" -eq {position} ] && {}\n", reply("$(_tcb_instances)"))),
+        Positional::Choice(choices) => body.push_str(&format!("{indent}[ \"$COMP_CWORD\" -eq {position} ] && {}\n", reply(&choices.join(" ")))),
+        Positional::ConfigKey => body.push_str(&format!("{indent}[ \"$COMP_CWORD\" -eq {position} ] && {}\n", reply(&config_keys()))),
+        Positional::Actions(actions) => {
+            let names: Vec<&str> = actions.iter().map(|a| a.name).collect();
+            body.push_str(&format!("{indent}if [ \"$COMP_CWORD\" -eq {position} ]; then\n{indent}    {}\n{indent}    return\n{indent}fi\n", reply(&names.join(" "))));
+            body.push_str(&format!("{indent}case \"${{COMP_WORDS[{position}]}}\" in\n"));
+            for action in actions {
+                body.push_str(&format!("{indent}    {})\n", action.names().join("|")));
+                body.push_str(&bash_method(action, position + 1, &format!("{indent}        ")));
+                body.push_str(&format!("{indent}        ;;\n"));
+            }
+            body.push_str(&format!("{indent}esac\n"));
+        }
+    }
+    body
+}
+
+/// Escapes text for use inside a single-quoted zsh _arguments spec
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]").replace(':', "\\:")
+}
+
+pub fn zsh() -> String {
+    let mut script = String::from("#compdef tcb tcobalt\n# zsh completion for tcb, generated by `tcb completions zsh`\n\n");
+    script.push_str("_tcb_instances() {\n    local -a instances\n    instances=(${(f)\"$($_tcb_command completions instances 2>/dev/null)\"})\n");
+    script.push_str("    instances=(${instances/$'\\t'/:})\n    _describe 'instance' instances\n}\n\n");
+    script.push_str("_tcb() {\n    local _tcb_command=$words[1]\n");
+    script.push_str(&zsh_methods(spec::METHODS, "method", "    "));
+    script.push_str("}\n\n_tcb \"$@\"\n");
+    script
+}
+
+/// Completes the method (or action) name at the current word, or hands the rest of the line to the method's completion
+fn zsh_methods(methods: &[Method], kind: &str, indent: &str) -> String {
+    let mut body = format!("{indent}if (( CURRENT == 2 )); then\n{indent}    local -a {kind}s\n{indent}    {kind}s=(\n");
+    for method in methods {
+        body.push_str(&format!("{indent}        '{}:{}'\n", method.name, zsh_escape(method.help)));
+    }
+    body.push_str(&format!("{indent}    )\n{indent}    _describe '{kind}' {kind}s\n{indent}    return\n{indent}fi\n"));
+    body.push_str(&format!("{indent}local {kind}=$words[2]\n{indent}words=(${{words[2,-1]}})\n{indent}(( CURRENT-- ))\n"));
+    body.push_str(&format!("{indent}case ${kind} in\n"));
+    for method in methods {
+        body.push_str(&format!("{indent}    {})\n", method.names().join("|")));
+        match method.positional {
+            Positional::Actions(actions) => body.push_str(&zsh_methods(actions, "action", &format!("{indent}        "))),
+            _ => body.push_str(&zsh_arguments(method, &format!("{indent}        ")))
+        }
+        body.push_str(&format!("{indent}        ;;\n"));
+    }
+    body.push_str(&format!("{indent}esac\n"));
+    body
+}
+
+fn zsh_arguments(method: &Method, indent: &str) -> String {
+    let mut specs: Vec<String> = Vec::new();
+    for flag in method.all_flags() {
+        let value = match flag.value {
+            Value::Switch => String::new(),
+            Value::Choice(choices) => format!(":{}:({})", flag.long, choices.join(" ")),
+            Value::File => String::from(":file:_files"),
+            Value::Dir => String::from(":directory:_files -/"),
+            Value::Instance => String::from(":instance:_tcb_instances"),
+            Value::Text => format!(":{}: ", flag.long)
+        };
+        let help = zsh_escape(flag.help);
+        // "-=" can't be written as an _arguments option, so --auto only gets its long form
+        match flag.short.filter(|s| s.is_ascii_alphabetic()) {
+            Some(short) => specs.push(format!("'(-{short} --{})'{{-{short},--{}}}'[{help}]{value}'", flag.long, flag.long)),
+            None => specs.push(format!("'--{}[{help}]{value}'", flag.long))
+        }
+    }
+    match method.positional {
+        Positional::Url => specs.push(String::from("'*:url:_urls'")),
+        Positional::File => specs.push(String::from("'1:file:_files'")),
+        Positional::Instance => specs.push(String::from("'1:instance:_tcb_instances'")),
+        Positional::Choice(choices) => specs.push(format!("'1:{}:({})'", method.name, choices.join(" "))),
+        Positional::ConfigKey => specs.push(format!("'1:key:({})'", config_keys())),
+        Positional::None | Positional::Actions(_) => ()
+    }
+    if specs.is_empty() {
+        return format!("{indent}_message 'no more arguments'\n");
+    }
+    let separator = format!(" \\\n{indent}    ");
+    format!("{indent}_arguments -s{separator}{}\n", specs.join(&separator))
+}
+
+/// Escapes text for use inside a single-quoted fish string
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+pub fn fish() -> String {
+    let mut script = String::from("# fish completion for tcb, generated by `tcb completions fish`\n\n");
+    script.push_str("function __tcb_instances\n    tcb completions instances 2>/dev/null\nend\n\n");
+    script.push_str("complete -c tcb -f\ncomplete -c tcobalt -w tcb\n\n");
+
+    let all_names: Vec<&str> = spec::METHODS.iter().flat_map(|m| m.names()).collect();
+    for method in spec::METHODS {
+        script.push_str(&format!("complete -c tcb -n 'not __fish_seen_subcommand_from {}' -a {} -d '{}'\n", all_names.join(" "), method.name, fish_escape(method.help)));
+    }
+    for method in spec::METHODS {
+        script.push('\n');
+        let condition = format!("__fish_seen_subcommand_from {}", method.names().join(" "));
+        match method.positional {
+            Positional::Actions(actions) => {
+                let action_names: Vec<&str> = actions.iter().flat_map(|a| a.names()).collect();
+                for action in actions {
+                    script.push_str(&format!(
+                        "complete -c tcb -n '{condition}; and not __fish_seen_subcommand_from {}' -a {} -d '{}'\n",
+                        action_names.join(" "), action.name, fish_escape(action.help)
+                    ));
+                }
+                for action in actions {
+                    let condition = format!("{condition}; and __fish_seen_subcommand_from {}", action.names().join(" "));
+                    script.push_str(&fish_method(action, &condition));
+                }
+            },
+            _ => script.push_str(&fish_method(method, &condition))
+        }
+    }
+    script
+}
+
+fn fish_method(method: &Method, condition: &str) -> String {
+    let mut lines = String::new();
+    match method.positional {
+        Positional::File => lines.push_str(&format!("complete -c tcb -n '{condition}' -F\n")),
+        Positional::Instance => lines.push_str(&format!("complete -c tcb -n '{condition}' -a '(__tcb_instances)'\n")),
+        Positional::Choice(choices) => lines.push_str(&format!("complete -c tcb -n '{condition}' -a '{}'\n", choices.join(" "))),
+        Positional::ConfigKey => lines.push_str(&format!("complete -c tcb -n '{condition}' -a '{}'\n", config_keys())),
+        Positional::None | Positional::Url | Positional::Actions(_) => ()
+    }
+    for flag in method.all_flags() {
+        let short = flag.short.filter(|s| s.is_ascii_alphabetic()).map(|s| format!(" -s {s}")).unwrap_or_default();
+        let value = match flag.value {
+            Value::Switch => String::new(),
+            Value::Choice(choices) => format!(" -x -a '{}'", choices.join(" ")),
+            Value::File => String::from(" -r -F"),
+            Value::Dir => String::from(" -x -a '(__fish_complete_directories)'"),
+            Value::Instance => String::from(" -x -a '(__tcb_instances)'"),
+            Value::Text => String::from(" -x")
+        };
+        lines.push_str(&format!("complete -c tcb -n '{condition}'{short} -l {}{value} -d '{}'\n", flag.long, fish_escape(flag.help)));
+    }
+    lines
+}
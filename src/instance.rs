@@ -0,0 +1,258 @@
+use crate::json::{self, JsonValue};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// What a cobalt instance reports about itself at its root endpoint.
+/// Every field is optional since older instances use a flat schema and newer ones may drop fields
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InstanceInfo {
+    pub version: Option<String>,
+    pub commit: Option<String>,
+    pub branch: Option<String>,
+    pub url: Option<String>,
+    /// Unix timestamp in milliseconds
+    pub start_time: Option<u128>,
+    /// Maximum media duration in seconds
+    pub duration_limit: Option<u64>,
+    pub services: Vec<String>,
+    pub turnstile: bool
+}
+impl InstanceInfo {
+    pub fn from_json(json: &HashMap<String, JsonValue>) -> Self {
+        // older instances put everything at the top level instead of in "cobalt" and "git"
+        let cobalt = json.get("cobalt").and_then(|c| c.get_object().ok()).unwrap_or(json.clone());
+        let git = json.get("git").and_then(|g| g.get_object().ok()).unwrap_or(json.clone());
+        let services = cobalt.get("services")
+            .and_then(|s| s.get_array().ok())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|s| s.get_str().ok())
+            .collect();
+
+        Self {
+            version: cobalt.get("version").and_then(|v| v.get_str().ok()),
+            commit: git.get("commit").and_then(|c| c.get_str().ok()),
+            branch: git.get("branch").and_then(|b| b.get_str().ok()),
+            url: cobalt.get("url").and_then(|u| u.get_str().ok()),
+            start_time: cobalt.get("startTime").and_then(number).and_then(|t| u128::try_from(t).ok()),
+            duration_limit: cobalt.get("durationLimit").and_then(number).and_then(|d| u64::try_from(d).ok()),
+            services,
+            turnstile: cobalt.get("turnstileSitekey").is_some_and(|t| t.get_str().is_ok())
+        }
+    }
+}
+
+/// Cobalt sends some numbers as strings, so accept either
+fn number(value: &JsonValue) -> Option<i128> {
+    match value {
+        JsonValue::Int(int) => Some(*int),
+        JsonValue::Float(float) => Some(*float as i128),
+        JsonValue::Str(string) => string.trim().parse().ok(),
+        _ => None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    pub instance: String,
+    pub latency: Duration,
+    pub info: Result<InstanceInfo, String>
+}
+
+pub async fn fetch_info(instance: &str, debug: bool) -> Result<InstanceInfo, String> {
+    let request = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?
+        .get(format!("https://{instance}/"))
+        .header("User-Agent", &format!("tcobalt {}", crate::VERSION.trim()))
+        .header("Accept", "application/json");
+
+    if debug { eprintln!("[DEBUG {instance}] Sending GET request to cobalt ...") };
+    let body = match request.send().await {
+        Ok(res) => res.text().await.map_err(|e| e.to_string())?,
+        Err(e) => return Err(format!("did not respond ({e})"))
+    };
+    if debug { eprintln!("[DEBUG {instance}] Response received, parsing json ...") };
+    let json = match json::parse(&body) {
+        Ok(json) => json,
+        Err(e) => {
+            if debug { eprintln!("[DEBUG {instance}] Cobalt returned response:\n{body}\n") };
+            return Err(format!("returned improper JSON ({e})"));
+        }
+    };
+    if !json.contains_key("cobalt") && !json.contains_key("version") {
+        return Err("did not return cobalt instance info".into());
+    }
+    Ok(InstanceInfo::from_json(&json))
+}
+
+/// Queries every instance at once, returning the reports in the same order as the given list
+pub async fn check_all(instances: &[String], debug: bool) -> Vec<HealthReport> {
+    let checks = instances.iter().map(|instance| async move {
+        let start = Instant::now();
+        let info = fetch_info(instance, debug).await;
+        HealthReport {
+            instance: instance.clone(),
+            latency: start.elapsed(),
+            info
+        }
+    });
+    futures::future::join_all(checks).await
+}
+
+/// Sorts the reports so that instances that responded come first, fastest to slowest.
+/// Instances that are down keep their original order at the end.
+pub fn rank(reports: &mut [HealthReport]) {
+    reports.sort_by(|a, b| match (a.info.is_ok(), b.info.is_ok()) {
+        (true, true) => a.latency.cmp(&b.latency),
+        (up_a, up_b) => up_b.cmp(&up_a)
+    });
+}
+
+pub fn print_table(reports: &[HealthReport], instances: &[String]) {
+    let width = reports.iter().map(|r| r.instance.len()).max().unwrap_or(0).max("INSTANCE".len());
+    println!("{:<3} {:<width$}  {:<6} {:>8}  {:<8} {:<9} {:<9} SERVICES", "#", "INSTANCE", "STATUS", "LATENCY", "VERSION", "COMMIT", "AUTH");
+    for report in reports {
+        let idx = instances.iter().position(|i| i == &report.instance).map(|i| (i + 1).to_string()).unwrap_or("-".into());
+        let latency = format!("{}ms", report.latency.as_millis());
+        match &report.info {
+            Ok(info) => {
+                let commit = info.commit.clone().unwrap_or("?".into());
+                println!(
+                    "{:<3} {:<width$}  {:<6} {:>8}  {:<8} {:<9} {:<9} {}",
+                    idx,
+                    report.instance,
+                    "up",
+                    latency,
+                    info.version.clone().unwrap_or("?".into()),
+                    commit.get(..7).unwrap_or(&commit),
+                    if info.turnstile { "turnstile" } else { "none" },
+                    info.services.len()
+                );
+                if !info.services.is_empty() {
+                    println!("{:<3} {:<width$}  {}", "", "", info.services.join(", "));
+                }
+            },
+            Err(e) => println!("{:<3} {:<width$}  {:<6} {:>8}  {e}", idx, report.instance, "down", latency)
+        }
+    }
+}
+
+pub fn print_json(reports: &[HealthReport]) {
+    let entries: Vec<String> = reports.iter().map(|report| {
+        let mut entry = format!(
+            "    {{\"instance\": {}, \"up\": {}, \"latency_ms\": {}",
+            json::escape(&report.instance),
+            report.info.is_ok(),
+            report.latency.as_millis()
+        );
+        match &report.info {
+            Ok(info) => {
+                let optional = |v: &Option<String>| v.as_ref().map(|v| json::escape(v)).unwrap_or("null".into());
+                let services: Vec<String> = info.services.iter().map(|s| json::escape(s)).collect();
+                entry.push_str(&format!(
+                    ", \"version\": {}, \"commit\": {}, \"branch\": {}, \"turnstile\": {}, \"services\": [{}]}}",
+                    optional(&info.version),
+                    optional(&info.commit),
+                    optional(&info.branch),
+                    info.turnstile,
+                    services.join(", ")
+                ));
+            },
+            Err(e) => entry.push_str(&format!(", \"error\": {}}}", json::escape(e)))
+        }
+        entry
+    }).collect();
+    println!("[\n{}\n]", entries.join(",\n"));
+}
+
+pub fn print_version(info: &InstanceInfo, instance: &str) {
+    let unknown = || String::from("unknown");
+    println!("Cobalt (by wukko and jj) version {}", info.version.clone().unwrap_or_else(unknown));
+    match (&info.branch, &info.commit) {
+        (Some(branch), Some(commit)) => println!("Latest commit on branch \"{branch}\": {commit}"),
+        (None, Some(commit)) => println!("Latest commit: {commit}"),
+        _ => println!("Latest commit: unknown")
+    }
+    println!("Instance URL: {}", info.url.clone().unwrap_or(format!("https://{instance}/")));
+    if let Some(start) = info.start_time {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(start);
+        let uptime = Duration::from_millis(now.saturating_sub(start) as u64);
+        println!("Running since: {} (up for {})", format_timestamp(start), format_duration(uptime));
+    }
+    if let Some(limit) = info.duration_limit {
+        println!("Duration limit: {}", format_duration(Duration::from_secs(limit)));
+    }
+    println!("Turnstile/auth required: {}", if info.turnstile { "yes" } else { "no" });
+    if !info.services.is_empty() {
+        println!("Supported services ({}): {}", info.services.len(), info.services.join(", "));
+    }
+}
+
+/// Formats a unix timestamp in milliseconds as a UTC date and time
+fn format_timestamp(millis: u128) -> String {
+    let secs = (millis / 1000) as i64;
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // civil-from-days conversion from Howard Hinnant's date algorithms
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC", rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes, seconds) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60, secs % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{seconds}s"),
+        (0, 0, _) => format!("{minutes}m {seconds}s"),
+        (0, _, _) => format!("{hours}h {minutes}m"),
+        _ => format!("{days}d {hours}h")
+    }
+}
+
+/// Lines up the services an instance supports with the list bundled with tcobalt.
+/// Each description is marked with ' ' if both have it, '-' if only tcobalt's list has it, and '+' if only the instance has it
+pub fn compare_services(services: &[String]) -> Vec<(char, String)> {
+    let bundled = crate::strings::supported_services();
+    let has = |name: &str| services.iter().any(|s| s.eq_ignore_ascii_case(name));
+
+    let mut lines: Vec<(char, String)> = bundled.iter().map(|(name, description)| {
+        (if has(name) { ' ' } else { '-' }, description.clone())
+    }).collect();
+    for service in services.iter().filter(|s| !bundled.iter().any(|(name, _)| name.eq_ignore_ascii_case(s))) {
+        lines.push(('+', service.clone()));
+    }
+    lines
+}
+
+/// Prints the services an instance supports, marking where they differ from the list bundled with tcobalt.
+/// An empty list prints the bundled list as-is
+pub fn print_services(services: &[String], instance: &str) {
+    if services.is_empty() {
+        crate::strings::supported_services().iter().for_each(|(_, description)| println!("{description}"));
+        return;
+    }
+
+    println!("Services supported by {instance}:");
+    let lines = compare_services(services);
+    for (mark, description) in lines.iter() {
+        println!("{mark} {description}");
+    }
+    if lines.iter().any(|(mark, _)| *mark != ' ') {
+        println!("\n(-) is in tcobalt's bundled list but not supported by this instance");
+        println!("(+) is supported by this instance but missing from tcobalt's bundled list");
+    }
+}
@@ -474,6 +474,10 @@ pub fn parse(json: impl ToString) -> Result<HashMap<String, JsonValue>, String>
                     if c == ',' && last_push_object {
                         closing_expect = false;
                         last_push_object = false;
+                        // a closed subobject inside of another object is followed by a key, not a value
+                        if history.last() != Some(&ReadHistory::Array) {
+                            key_level = 0;
+                        }
                         continue;
                     }
                     if closing_expect {
@@ -708,6 +712,24 @@ impl JsonValue {
     }
 }
 
+/// Quotes and escapes a string so it can be placed inside of generated JSON
+pub fn escape(string: &str) -> String {
+    let mut escaped = String::from('"');
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
 fn sub_without_overflow(num: usize, subtractor: usize) -> usize {
     if num < subtractor {
         0
@@ -2,14 +2,17 @@ mod json;
 mod args;
 mod strings;
 mod process;
+mod instance;
+mod service;
+mod archive;
+mod completions;
+mod manpage;
 
 use process as proc;
 use args as tcargs;
 use args::Args;
 
 use std::pin::Pin;
-use std::sync::Arc;
-use tokio::sync::RwLock;
 
 const VERSION: &str = include_str!("version");
 
@@ -25,7 +28,7 @@ async fn main() -> std::process::ExitCode {
     let args = match Args::get().parse() {
         Ok(parsed) => parsed,
         Err(err) => {
-            eprintln!("Invalid command syntax");
+            eprintln!("{}", err.title());
             eprintln!("{}", err.print());
             return std::process::ExitCode::FAILURE;
         },
@@ -33,94 +36,173 @@ async fn main() -> std::process::ExitCode {
     if let Some(help_flag) = args.help_flag {
         match help_flag {
             args::types::Help::Help => println!("{}", strings::get_help()),
-            args::types::Help::List => println!("{}", strings::get_str("usage", "list")),
-            args::types::Help::Bulk => println!("{}", strings::get_str("usage", "bulk")),
-            args::types::Help::Get => println!("{}", strings::get_str("usage", "get")),
-            args::types::Help::Examples => println!("{}", strings::get_str("usage", "examples")),
-            args::types::Help::GenConfig => println!("{}", strings::get_str("usage", "gen-config")),
-            args::types::Help::Config => println!("{}", strings::get_str("usage", "config")),
+            args::types::Help::List => println!("{}", tcargs::spec::usage("list")),
+            args::types::Help::Bulk => println!("{}", tcargs::spec::usage("bulk")),
+            args::types::Help::Get => println!("{}", tcargs::spec::usage("get")),
+            args::types::Help::Examples => println!("{}", tcargs::spec::usage("examples")),
+            args::types::Help::GenConfig => println!("{}", tcargs::spec::usage("gen-config")),
+            args::types::Help::Config => println!("{}", tcargs::spec::usage("config")),
+            args::types::Help::Instances => println!("{}", tcargs::spec::usage("instances")),
+            args::types::Help::Url => println!("{}", tcargs::spec::usage("url")),
+            args::types::Help::Info => println!("{}", tcargs::spec::usage("info")),
+            args::types::Help::Completions => println!("{}", tcargs::spec::usage("completions")),
+            args::types::Help::Man => println!("{}", tcargs::spec::usage("man")),
+            args::types::Help::Environment => println!("{}", tcargs::spec::usage("environment")),
         }
         return std::process::ExitCode::SUCCESS;
     }
     match args.method.clone().expect("Failed to catch invalid method early") {
         args::types::Method::Get => {
+            if args.dry_run {
+                if !print_dry_run(&args, 0) {
+                    return std::process::ExitCode::FAILURE;
+                }
+                return std::process::ExitCode::SUCCESS;
+            }
             if debug { eprintln!("[DEBUG] Executing GET method\n") };
-            let success = execute_get_media(args, 0, debug).await;
+            let outcome = execute_get_media(args, 0, debug).await;
             if debug { eprintln!("\n[DEBUG] GET method is complete") };
-            if !success {
+            if outcome.is_error() {
+                return std::process::ExitCode::FAILURE;
+            }
+        },
+        args::types::Method::Url => {
+            if debug { eprintln!("[DEBUG] Executing URL method\n") };
+            if !execute_print_url(args, debug).await {
+                return std::process::ExitCode::FAILURE;
+            }
+        },
+        args::types::Method::Info => {
+            if debug { eprintln!("[DEBUG] Executing INFO method\n") };
+            if !execute_print_info(args, debug).await {
                 return std::process::ExitCode::FAILURE;
             }
         },
         args::types::Method::Bulk => {
-            let failed: Arc<RwLock<bool>> = Arc::new(RwLock::new(false));
-            let mut futures_array: Vec<Pin<Box<dyn std::future::Future<Output = ()>>>> = Vec::new();
+            for warning in args.warnings.iter() {
+                eprintln!("{warning}");
+            }
+            let mut futures_array: Vec<Pin<Box<dyn std::future::Future<Output = proc::Outcome>>>> = Vec::new();
             let mut i = 0;
             if debug { eprintln!("[DEBUG] Collecting bulk tasks ...") };
-            args.bulk_array.unwrap().iter().for_each(|a| {
+            let bulk_array = args.bulk_array.clone().unwrap();
+            bulk_array.iter().for_each(|a| {
                 if args.same_filenames {
                     i += 1;
                 }
                 let args = a.clone();
-                let switch = Arc::clone(&failed);
                 let task = async move {
-                    let success = execute_get_media(args, i, debug).await;
-                    if !success {
-                        let mut lock = switch.write().await;
-                        *lock = true;
+                    if !args.dry_run {
+                        return execute_get_media(args, i, debug).await;
+                    }
+                    match print_dry_run(&args, i) {
+                        true => proc::Outcome::Skipped(String::from("dry run")),
+                        false => proc::Outcome::Failed(String::from("the link would be rejected"))
                     }
                 };
                 futures_array.push(Box::pin(task));
             });
 
             if debug { eprintln!("[DEBUG] Executing all tasks asynchronously ...\n") };
-            futures::future::join_all(futures_array).await;
+            let started = std::time::Instant::now();
+            let outcomes = futures::future::join_all(futures_array).await;
             if debug { eprintln!("\n[DEBUG] Execution has completed") };
+            print_bulk_summary(&bulk_array, &outcomes, &args.bulk_duplicates, started.elapsed());
 
-            if failed.read().await.clone() == true {
+            let failed_lines: Vec<&str> = args.bulk_lines.iter().zip(outcomes.iter())
+                .filter(|(_, outcome)| outcome.is_failed())
+                .map(|(line, _)| line.as_str())
+                .collect();
+            if let Some(retry_file) = &args.retry_file {
+                if failed_lines.is_empty() && args.dry_run {
+                    println!("Nothing would fail, so no retry file was written");
+                } else if failed_lines.is_empty() {
+                    // a retry file left over from an earlier run would download what has succeeded since
+                    match std::fs::remove_file(proc::expand_home(retry_file)) {
+                        Ok(()) => println!("Nothing failed, so the old retry file {retry_file} was removed"),
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => println!("Nothing failed, so no retry file was written"),
+                        Err(e) => eprintln!("Unable to remove the old retry file {retry_file}: {e}")
+                    }
+                } else {
+                    match std::fs::write(proc::expand_home(retry_file), failed_lines.join("\n") + "\n") {
+                        Ok(()) => println!("Wrote the {} failed line(s) to {retry_file}, run `tcb bulk execute {retry_file}` to retry them", failed_lines.len()),
+                        Err(e) => eprintln!("Unable to write the retry file {retry_file}: {e}")
+                    }
+                }
+            }
+            if outcomes.iter().any(|outcome| outcome.is_error()) {
                 return std::process::ExitCode::FAILURE;
             }
         },
-        args::types::Method::List => println!("{}", strings::get_str("info", "supported")),
+        args::types::Method::List(api_url) => {
+            match instance::fetch_info(&api_url, debug).await {
+                Ok(info) if !info.services.is_empty() => instance::print_services(&info.services, &api_url),
+                Ok(_) => {
+                    eprintln!("Cobalt server {api_url} did not list its services, showing the services bundled with tcobalt instead");
+                    instance::print_services(&[], &api_url);
+                },
+                Err(e) => {
+                    eprintln!("Cobalt server {api_url} {e}");
+                    eprintln!("Showing the services bundled with tcobalt instead");
+                    instance::print_services(&[], &api_url);
+                }
+            }
+        },
         args::types::Method::Help => unreachable!(),
         args::types::Method::Version => println!("{}", strings::get_str("info", "version").replace("{}", VERSION.trim())),
         args::types::Method::CobaltVersion(api_url) => {
-            let request = reqwest::Client::new().get(format!("https://{}/", api_url))
-                .header("User-Agent", &format!("tcobalt {}", VERSION.trim()));
-            if debug { eprintln!("[DEBUG] Sending GET request to cobalt ...") };
-            let ver = match request.send().await {
-                Ok(res) => {
-                    res.text().await.expect("cobalt returned nothing")
-                },
+            match instance::fetch_info(&api_url, debug).await {
+                Ok(info) => instance::print_version(&info, &api_url),
                 Err(e) => {
-                    eprintln!("Cobalt server did not respond: {}", e.to_string());
+                    eprintln!("Cobalt server {api_url} {e}");
                     return std::process::ExitCode::FAILURE;
                 }
-            };
-            if debug { eprintln!("[DEBUG] Response received, parsing json ...") };
-            let stats = match json::parse(&ver) {
-                Ok(j) => j,
-                Err(e) => {
-                    eprintln!("{}", proc::print_json_error(e, ver));
-                    return std::process::ExitCode::FAILURE;
+            }
+        },
+        args::types::Method::Instances => {
+            if args.instance_list.is_empty() {
+                eprintln!("There are no instances in the [default.instances] section of your config file");
+                eprintln!("Run `tcb gen-config` to write the default list, or `tcb help config` for more information");
+                return std::process::ExitCode::FAILURE;
+            }
+
+            if debug { eprintln!("[DEBUG] Checking {} instances ...", args.instance_list.len()) };
+            let mut reports = instance::check_all(&args.instance_list, debug).await;
+            if args.reorder_instances {
+                instance::rank(&mut reports);
+            }
+            if args.json_output {
+                instance::print_json(&reports);
+            } else {
+                instance::print_table(&reports, &args.instance_list);
+            }
+
+            if args.reorder_instances {
+                let ranked: Vec<String> = reports.iter().map(|r| r.instance.clone()).collect();
+                match tcargs::config::write_instances(&ranked) {
+                    Ok(path) => eprintln!("Reordered the instances in {} by health", path.to_string_lossy()),
+                    Err(e) => {
+                        eprintln!("Unable to rewrite the config file: {e}");
+                        return std::process::ExitCode::FAILURE;
+                    }
                 }
-            };
-            let cobalt = stats.get("cobalt").unwrap().get_object().unwrap();
-            let git = stats.get("git").unwrap().get_object().unwrap();
-            let version = cobalt.get("version").unwrap().get_str().unwrap();
-            let commit = git.get("commit").unwrap().get_str().unwrap();
-            let branch = git.get("branch").unwrap().get_str().unwrap();
-            println!("Cobalt (by wukko and jj) version {version}");
-            println!("Latest commit on branch \"{branch}\": {commit}");
+            }
+            if reports.iter().all(|r| r.info.is_err()) {
+                return std::process::ExitCode::FAILURE;
+            }
+        },
+        args::types::Method::Completions(shell) => completions::print(&shell),
+        args::types::Method::Config(action) => match tcargs::config::run(&action, args.profile.as_deref()) {
+            Ok(output) => println!("{output}"),
+            Err(e) => {
+                eprintln!("{e}");
+                return std::process::ExitCode::FAILURE;
+            }
         },
+        args::types::Method::Man => print!("{}", manpage::render(VERSION.trim())),
         args::types::Method::GenConfig => {
-            let text = strings::get_str("info", "default-config").replace("\\", "");
-            let path = std::path::PathBuf::from({
-                if cfg!(target_os = "windows") {
-                    "$CFG\\tcobalt.conf".replace("$CFG", &std::env::var("LOCALAPPDATA").expect("no localappdata var"))
-                } else {
-                    "$CFG/.config/tcobalt.conf".replace("$CFG", &std::env::var("HOME").expect("no home var"))
-                }
-            });
+            let text = format!("{}\n{}", tcargs::spec::default_config(), strings::get_str("info", "default-instances").replace("\\", ""));
+            let path = tcargs::config::config_path();
 
             if let Ok(()) = std::fs::write(&path, text) {
                 println!("Wrote default config to {}", path.to_string_lossy());
@@ -133,34 +215,30 @@ async fn main() -> std::process::ExitCode {
     std::process::ExitCode::SUCCESS
 }
 
-async fn execute_get_media(args: Args, bulk: u16, debug: bool) -> bool {
-    let json = proc::cobalt_args(&args);
+async fn execute_get_media(args: Args, bulk: u16, debug: bool) -> proc::Outcome {
     let download_url: &str = args.c_url.as_ref().unwrap();
+    let archive_entry = archive::entry(&args);
+    if let Some(archive) = &args.archive {
+        if !args.force && archive::contains(archive, &archive_entry) {
+            status!(args, "Skipping {download_url}, it is already in the archive {archive}");
+            return proc::Outcome::Skipped(format!("already in the archive {archive}"));
+        }
+    }
 
-    let request = reqwest::Client::new().post(format!("https://{}/", &args.cobalt_instance))
-        .header("User-Agent", &format!("tcobalt {}", VERSION.trim()))
-        .header("Accept", "application/json")
-        .header("Content-Type", "application/json")
-        .body(json);
-
-    if debug { eprintln!("[DEBUG {download_url}] Sending POST request to cobalt server ...") };
-    let res = attempt!(request.send().await, "Cobalt server did not respond:\n\"{}\"\n(when downloading from {download_url})");
-
-    let body = res.text().await.unwrap();
-    if debug { eprintln!("[DEBUG {download_url}] Response received, parsing json ...") };
-    let json = attempt!(json::parse(&body), proc::print_json_error("{}".into(), body));
+    let (json, _) = match proc::request_cobalt(&args, debug).await {
+        Ok(res) => res,
+        Err(e) => return proc::fail(e)
+    };
 
     let status = json.get("status".into()).unwrap().get_str().unwrap();
     match status.as_str() {
-        "error" => {
-            let text = json.get("error".into()).unwrap().get_object().unwrap().get("code".into()).unwrap().get_str().unwrap();
-            eprintln!("Cobalt returned error: \"{text}\" (when downloading from {download_url})");
-            return false;
-        },
         "tunnel" | "redirect" | "picker" => {
             if debug { eprintln!("[DEBUG {download_url}] Cobalt returned a response") };
 
-            let url = proc::get_url(&args, &status, &json);
+            let url = match proc::get_url(&args, &status, &json) {
+                Ok(url) => url,
+                Err(e) => return proc::fail(format!("{e}\n(when downloading from {download_url})"))
+            };
 
             let media = if args.c_download_mode == tcargs::types::DownloadMode::Audio {
                 "audio"
@@ -174,38 +252,285 @@ async fn execute_get_media(args: Args, bulk: u16, debug: bool) -> bool {
             let res = attempt!(stream_request.send().await, "Live renderer did not respond:\n\"{}\"\n(when downloading from {download_url})");
 
             if debug { eprintln!("[DEBUG {download_url}] Response received from stream") };
-            let mut filename = json.get("filename".into()).unwrap().get_str().unwrap();
-            if let Some(custom_name) = args.out_filename {
-                filename = custom_name;
-            }
-            if bulk > 0 {
-                filename = format!("{bulk}_{filename}");
+            if args.stdout {
+                status!(args, "Streaming {media} from {download_url} to stdout ...");
+                let mut res = res;
+                let mut out = std::io::stdout().lock();
+                let mut bytes = 0;
+                loop {
+                    let chunk = match attempt!(res.chunk().await, "Error decoding byte stream:\n\"{}\"\n(when downloading from {download_url})") {
+                        Some(chunk) => chunk,
+                        None => break
+                    };
+                    match std::io::Write::write_all(&mut out, &chunk) {
+                        Ok(()) => bytes += chunk.len() as u64,
+                        // the reader closed the pipe, like a player that quit early
+                        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return proc::Outcome::Success(bytes),
+                        Err(e) => return proc::fail(format!("Unable to write data to stdout:\n\"{e}\"\n(when downloading from {download_url})"))
+                    }
+                }
+                attempt!(std::io::Write::flush(&mut out), "Unable to write data to stdout:\n\"{}\"\n(when downloading from {download_url})");
+                status!(args, "Your {media} has been written to stdout!");
+                if args.exec.is_some() {
+                    eprintln!("Warning: --exec is ignored when writing to stdout, since there is no file to run it on");
+                }
+                if let Some(archive) = &args.archive {
+                    if let Err(e) = archive::record(archive, &archive_entry) {
+                        eprintln!("Unable to record {download_url} in the archive {archive}: {e}");
+                    }
+                }
+                return proc::Outcome::Success(bytes);
             }
+            let filename = json.get("filename").unwrap().get_str().unwrap();
+            let output = match proc::output_path(&args, &filename, bulk).and_then(|path| proc::resolve_collision(path, &args.if_exists)) {
+                Ok(Some(output)) => output,
+                Ok(None) => {
+                    println!("Skipping {download_url}, the file it would be written to already exists");
+                    return proc::Outcome::Skipped(String::from("the output file already exists"));
+                },
+                Err(e) => return proc::fail(format!("{e}\n(when downloading from {download_url})"))
+            };
             println!(
                 "Downloading {} from {} ...", 
                 media,
                 download_url
             );
-            let stream = attempt!(res.bytes().await, "Error decoding byte stream:\n\"{}\"\n(when downloading from {download_url})");
+            let path = output.path.clone();
+            let stream = match res.bytes().await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    if output.created {
+                        let _ = std::fs::remove_file(&path);
+                    }
+                    return proc::fail(format!("Error decoding byte stream:\n\"{e}\"\n(when downloading from {download_url})"));
+                }
+            };
+            let bytes = stream.len() as u64;
 
-            let display = match filename.contains(' ') {
+            let shown = path.strip_prefix(std::env::current_dir().unwrap_or_default()).unwrap_or(&path).to_string_lossy().to_string();
+            let display = match shown.contains(' ') {
                 true => {
-                    format!("'{}'", &filename)
+                    format!("'{}'", &shown)
                 },
-                false => filename.clone(),
+                false => shown.clone(),
             };
 
             println!("Data downloaded successfully! Writing {media} to {} ...", &display);
-            let path = std::env::current_dir().unwrap().join(&filename);
-            attempt!(tokio::fs::write(path, stream).await, "Unable to write data to file:\n\"{}\"\n(when writing to {display})");
+            let mut file = output.file;
+            attempt!(file.set_len(0).and_then(|_| std::io::Write::write_all(&mut file, &stream)), "Unable to write data to file:\n\"{}\"\n(when writing to {display})");
+
+            println!("Your {media} is ready! >> {display}");
+            // the file is there whatever the command does, so it is archived first and a failed command doesn't make it download again
+            if let Some(archive) = &args.archive {
+                if let Err(e) = archive::record(archive, &archive_entry) {
+                    eprintln!("Unable to record {download_url} in the archive {archive}: {e}");
+                }
+            }
+            if args.exec.is_some() {
+                if let Err(e) = proc::run_exec(&args, &path).await {
+                    eprintln!("{e}\n(after downloading from {download_url})");
+                    return proc::Outcome::CommandFailed(bytes, e);
+                }
+            }
+            proc::Outcome::Success(bytes)
+        },
+        _ => unreachable!()
+    }
+}
+
+/// Prints a row for every link, with the duplicates that were dropped before downloading listed as skipped after the rest
+fn print_bulk_summary(bulk_array: &[Args], outcomes: &[proc::Outcome], duplicates: &[(String, String)], elapsed: std::time::Duration) {
+    let duplicate_outcomes: Vec<proc::Outcome> = duplicates.iter().map(|(_, reason)| proc::Outcome::Skipped(reason.clone())).collect();
+    let outcomes: Vec<&proc::Outcome> = outcomes.iter().chain(&duplicate_outcomes).collect();
+    let count = |f: fn(&proc::Outcome) -> bool| outcomes.iter().filter(|o| f(o)).count();
+    let succeeded = count(|o| matches!(o, proc::Outcome::Success(_) | proc::Outcome::CommandFailed(..)));
+    let commands = count(|o| matches!(o, proc::Outcome::CommandFailed(..)));
+    let skipped = count(|o| matches!(o, proc::Outcome::Skipped(_)));
+    let failed = count(|o| o.is_failed());
+    let bytes: u64 = outcomes.iter().map(|o| match o {
+        proc::Outcome::Success(bytes) | proc::Outcome::CommandFailed(bytes, _) => *bytes,
+        _ => 0
+    }).sum();
+
+    let urls: Vec<String> = bulk_array.iter().map(|a| a.c_url.clone().unwrap_or_default()).chain(duplicates.iter().map(|(url, _)| url.clone())).collect();
+    let width = urls.iter().map(|u| u.len()).max().unwrap_or(0).max("URL".len());
+    println!("\n{:<4} {:<8} {:<width$}  DETAIL", "#", "STATUS", "URL");
+    for (i, (url, outcome)) in urls.iter().zip(outcomes).enumerate() {
+        let (status, detail) = match outcome {
+            proc::Outcome::Success(bytes) => ("ok", format_bytes(*bytes)),
+            proc::Outcome::CommandFailed(bytes, reason) => ("ok", format!("{}, downloaded but the command failed: {}", format_bytes(*bytes), reason.replace('\n', " "))),
+            proc::Outcome::Skipped(reason) => ("skipped", reason.clone()),
+            // the url is already in the table, so drop the "(when downloading from ...)" lines
+            proc::Outcome::Failed(reason) => ("failed", reason.lines().filter(|l| !l.starts_with('(')).collect::<Vec<&str>>().join(" "))
+        };
+        println!("{:<4} {:<8} {:<width$}  {detail}", i + 1, status, url);
+    }
+    println!(
+        "\n{succeeded} succeeded{}, {skipped} skipped, {failed} failed | {} downloaded in {:.1}s",
+        if commands > 0 { format!(" ({commands} with a failed command)") } else { String::new() },
+        format_bytes(bytes),
+        elapsed.as_secs_f64()
+    );
+}
+
+fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{bytes} B") } else { format!("{size:.1} {}", units[unit]) }
+}
+
+/// Shows everything a download would do without contacting cobalt. Returns false if the link would be rejected
+fn print_dry_run(args: &Args, bulk: u16) -> bool {
+    let download_url = args.c_url.clone().unwrap();
+    println!("{download_url}");
+    let detected = match service::check_url(&download_url) {
+        Ok(detected) => detected,
+        Err(e) => {
+            println!("  error: {e}\n");
+            return false;
+        }
+    };
+    println!("  service: {}", detected.map(|s| s.print()).unwrap_or("unknown".into()));
+    for warning in service::flag_warnings(args, detected) {
+        println!("  warning: {warning}");
+    }
+
+    let mut instances = vec![args.cobalt_instance.clone()];
+    if args.failover {
+        instances.extend(args.instance_list.iter().filter(|i| **i != args.cobalt_instance).cloned());
+    }
+    println!("  instance: {}", instances.join(" -> "));
+
+    if args.stdout {
+        println!("  output: stdout");
+    } else {
+        // the parts cobalt decides are left as fields, since the filename isn't known without asking it
+        match proc::output_path(args, "{title}.{ext}", bulk) {
+            Ok(path) => println!("  output: {} (if it exists: {})", path.to_string_lossy(), args.if_exists.print()),
+            Err(e) => {
+                println!("  error: {e}\n");
+                return false;
+            }
+        }
+    }
+    if let Some(archive) = &args.archive {
+        let listed = archive::contains(archive, &archive::entry(args));
+        println!("  archive: {archive}{}", if listed && !args.force { " (already downloaded, would be skipped)" } else { "" });
+    }
+    if let Some(exec) = &args.exec {
+        println!("  exec: {exec}");
+    }
+    println!("  request: {}\n", proc::cobalt_args(args));
+    true
+}
 
-            println!("Your {media} is ready! >> {display}")
+async fn execute_print_url(args: Args, debug: bool) -> bool {
+    let download_url = args.c_url.clone().unwrap();
+    let (json, instance) = match proc::request_cobalt(&args, debug).await {
+        Ok(res) => res,
+        Err(e) => {
+            eprintln!("{e}");
+            return false;
+        }
+    };
+
+    let status = json.get("status").unwrap().get_str().unwrap();
+    let media = if args.c_download_mode == tcargs::types::DownloadMode::Audio { "audio" } else { "video" };
+    let items: Vec<proc::PickerItem> = match status.as_str() {
+        "picker" if args.picker_choice == 0 => proc::picker_items(&json),
+        "tunnel" | "redirect" | "picker" => match proc::get_url(&args, &status, &json) {
+            Ok(url) => vec![proc::PickerItem { kind: media.into(), url, thumb: None }],
+            Err(e) => {
+                eprintln!("{e}\n(when downloading from {download_url})");
+                return false;
+            }
         },
         _ => unreachable!()
+    };
+    let filename = json.get("filename").and_then(|f| f.get_str().ok());
+    let output = filename.as_ref().and_then(|f| proc::output_path(&args, f, 0).ok());
+
+    if args.json_output {
+        let optional = |v: Option<String>| v.map(|v| json::escape(&v)).unwrap_or("null".into());
+        let urls: Vec<String> = items.iter()
+            .map(|item| format!("{{\"type\": {}, \"url\": {}}}", json::escape(&item.kind), json::escape(&item.url)))
+            .collect();
+        println!(
+            "{{\"url\": {}, \"service\": {}, \"instance\": {}, \"status\": {}, \"filename\": {}, \"output\": {}, \"urls\": [{}]}}",
+            json::escape(&download_url),
+            optional(service::detect(&download_url).map(|s| s.print())),
+            json::escape(&instance),
+            json::escape(&status),
+            optional(filename),
+            optional(output.map(|o| o.to_string_lossy().to_string())),
+            urls.join(", ")
+        );
+        return true;
+    }
+
+    for item in items.iter() {
+        println!("{}", item.url);
+    }
+    if items.len() > 1 {
+        let kinds: Vec<&str> = items.iter().map(|item| item.kind.as_str()).collect();
+        eprintln!("Cobalt returned {} items to pick from ({}), choose one with -p", items.len(), kinds.join(", "));
+    }
+    if let Some(output) = output {
+        eprintln!("Filename: {}", output.to_string_lossy());
     }
     true
 }
 
+async fn execute_print_info(args: Args, debug: bool) -> bool {
+    println!("Request body:\n{}\n", proc::cobalt_args(&args));
+    let (json, instance) = match proc::request_cobalt(&args, debug).await {
+        Ok(res) => res,
+        Err(e) => {
+            eprintln!("{e}");
+            return false;
+        }
+    };
+
+    let text = |key: &str| json.get(key).and_then(|v| v.get_str().ok());
+    let status = text("status").unwrap_or("unknown".into());
+    println!("Answered by: {instance}");
+    println!("Status: {status}");
+    if let Some(filename) = text("filename") {
+        println!("Filename: {filename}");
+    }
+    if let Some(url) = text("url") {
+        println!("Link: {url}");
+    }
+
+    if status == "picker" {
+        match (text("audio"), text("audioFilename")) {
+            (Some(audio), Some(filename)) => println!("Audio track: {audio}\nAudio filename: {filename}"),
+            (Some(audio), None) => println!("Audio track: {audio}"),
+            _ => println!("Audio track: none")
+        }
+        let items = proc::picker_items(&json);
+        println!("Items to pick from ({}):", items.len());
+        for (i, item) in items.iter().enumerate() {
+            println!("  {}. {} {}", i + 1, item.kind, item.url);
+            if let Some(thumb) = &item.thumb {
+                println!("     thumbnail: {thumb}");
+            }
+        }
+    } else {
+        let audio = match args.c_download_mode {
+            tcargs::types::DownloadMode::Mute => "removed (--mute-audio)",
+            tcargs::types::DownloadMode::Audio => "audio only",
+            tcargs::types::DownloadMode::Auto => "included in the media"
+        };
+        println!("Audio: {audio}");
+    }
+    true
+}
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,116 @@
+use crate::args::spec;
+use crate::strings;
+
+/// Renders the tcb(1) man page in roff from the usage strings, so it never says anything "tcb help" doesn't
+pub fn render(version: &str) -> String {
+    let mut page = format!(".TH TCB 1 \"\" \"tcobalt {}\" \"User Commands\"\n", escape(version));
+    page.push_str(".SH NAME\ntcb \\- download media from the internet with cobalt, straight from your terminal\n");
+
+    page.push_str(".SH SYNOPSIS\n.nf\n");
+    for method in spec::METHODS {
+        page.push_str(&format!("{}\n", escape(&synopsis(method, "tcb"))));
+    }
+    page.push_str(".fi\n");
+
+    page.push_str(".SH DESCRIPTION\n");
+    let help = strings::get_help();
+    for line in help.lines().take_while(|line| !line.starts_with("Usage:")) {
+        page.push_str(&format!("{}\n", escape(line)));
+    }
+
+    page.push_str(".SH METHODS\n");
+    for method in spec::METHODS {
+        page.push_str(&format!(".SS {}\n", escape(&method.names().join(", "))));
+        let section = spec::usage(method.name);
+        match section.lines().next() {
+            Some(title) if title == format!("tcobalt \"{}\" method syntax", method.name) => {
+                let body: Vec<&str> = section.lines().skip(1).filter(|line| !line.starts_with("Usage:")).collect();
+                page.push_str(&render_section(&body.join("\n")));
+            },
+            _ => page.push_str(&format!("{}\n", escape(method.help)))
+        }
+    }
+
+    page.push_str(".SH CONFIGURATION\n");
+    page.push_str(&render_section(&spec::usage("config")));
+    page.push_str(".PP\nThe default configuration file, as written by \\fBtcb gen\\-config\\fR:\n.PP\n.RS\n.nf\n");
+    let default_config = format!("{}\n{}", spec::default_config(), strings::get_str("info", "default-instances").replace('\\', ""));
+    for line in default_config.lines() {
+        page.push_str(&format!("{}\n", escape(line)));
+    }
+    page.push_str(".fi\n.RE\n");
+
+    page.push_str(".SH ENVIRONMENT\n");
+    page.push_str(&render_section(&strings::get_str("usage", "environment")));
+
+    page.push_str(".SH EXAMPLES\n.nf\n");
+    for line in strings::get_str("usage", "examples").lines().skip(1) {
+        page.push_str(&format!("{}\n", escape(line)));
+    }
+    page.push_str(".fi\n");
+
+    page.push_str(".SH COPYRIGHT\n");
+    for line in strings::get_str("info", "version").lines().skip(1) {
+        page.push_str(&format!("{}\n.br\n", escape(line)));
+    }
+    page
+}
+
+/// The usage line of a method, with one line per action for bulk
+fn synopsis(method: &spec::Method, command: &str) -> String {
+    let command = format!("{command} {}", method.name);
+    let args = match method.positional {
+        spec::Positional::None => String::new(),
+        spec::Positional::Url => String::from(" <url>"),
+        spec::Positional::Instance => String::from(" [instance]"),
+        spec::Positional::File => String::from(" <file>"),
+        spec::Positional::Choice(choices) => format!(" <{}>", choices.join("|")),
+        spec::Positional::ConfigKey => String::from(" <key>"),
+        spec::Positional::Actions(actions) => {
+            return actions.iter().map(|action| synopsis(action, &command)).collect::<Vec<String>>().join("\n");
+        }
+    };
+    let options = if method.flags.is_empty() { "" } else { " [options]" };
+    format!("{command}{args}{options}")
+}
+
+/// Turns a usage section into roff.
+/// Lines with a single tab and a gap in them, like options, become tagged paragraphs, and lines with 3 or more tabs continue the one before.
+/// Other indented lines keep their indent, and unindented lines ending with : become bold headings
+fn render_section(section: &str) -> String {
+    let mut roff = String::new();
+    for line in section.lines() {
+        let text = line.trim();
+        let tabs = line.chars().take_while(|c| *c == '\t').count();
+        if text.is_empty() {
+            roff.push_str(".PP\n");
+        } else if text.ends_with(':') && tabs == 0 {
+            roff.push_str(&format!(".PP\n.B {}\n", escape(text)));
+        } else if tabs >= 3 {
+            roff.push_str(&format!(".br\n{}\n", escape(text)));
+        } else if let (true, Some((tag, description))) = (tabs > 0, split_entry(text)) {
+            roff.push_str(&format!(".TP\n\\fB{}\\fR\n{}\n", escape(tag), escape(description)));
+        } else if tabs > 0 {
+            roff.push_str(&format!(".br\n{}{}\n", "\\ ".repeat(4 * (tabs - 1)), escape(text)));
+        } else {
+            roff.push_str(&format!("{}\n.br\n", escape(text)));
+        }
+    }
+    roff.replace(".br\n.PP\n", ".PP\n").replace(".PP\n.PP\n", ".PP\n")
+}
+
+/// Splits "-q --vquality <quality>    The quality..." at the first tab or run of spaces
+fn split_entry(text: &str) -> Option<(&str, &str)> {
+    let gap = text.find('\t').into_iter().chain(text.find("  ")).min()?;
+    Some((text[..gap].trim(), text[gap..].trim()))
+}
+
+/// Escapes text so roff prints it as-is
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    if text.starts_with('.') || text.starts_with('\'') {
+        format!("\\&{text}")
+    } else {
+        text
+    }
+}
@@ -1,5 +1,6 @@
-use crate::{tcargs, args::Args, json};
+use crate::{tcargs, args::Args, json, service};
 use std::io::Write;
+use std::collections::HashMap;
 
 pub fn print_json_error(error: String, body: String) -> String {
     let mut text = String::new();
@@ -15,7 +16,248 @@ pub fn print_json_error(error: String, body: String) -> String {
     text
 }
 
-pub fn get_url(args: &Args, status: &str, json: &std::collections::HashMap<String, json::JsonValue>) -> String {
+/// Expands a leading ~ into the home directory, since paths from the config file don't go through a shell
+pub fn expand_home(path: &str) -> std::path::PathBuf {
+    let home = if cfg!(target_os = "windows") { std::env::var("USERPROFILE") } else { std::env::var("HOME") };
+    if let Ok(home) = home {
+        if path == "~" {
+            return std::path::PathBuf::from(home);
+        }
+        if let Some(rest) = path.strip_prefix("~/").or(path.strip_prefix("~\\")) {
+            return std::path::PathBuf::from(home).join(rest);
+        }
+    }
+    std::path::PathBuf::from(path)
+}
+
+/// Fields that can be used in an output filename template, like `-o {service}/{title}.{ext}`
+pub const TEMPLATE_FIELDS: &[&str] = &["filename", "title", "ext", "service", "id", "index", "type"];
+
+/// Fields that can be used in an --exec command template
+pub const EXEC_FIELDS: &[&str] = &["path", "url", "service", "type"];
+
+/// Replaces every {field} in the template, failing on fields that don't exist or braces that aren't closed
+pub fn render_template(template: &str, fields: &[(&str, String)]) -> Result<String, String> {
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => return Err(format!("Unclosed {{ in template: {template}"))
+        };
+        let name = &rest[start+1..end];
+        match fields.iter().find(|(field, _)| *field == name) {
+            Some((_, value)) => output.push_str(value),
+            None => return Err(format!("Unknown field {{{name}}} in template: {template}\nAvailable fields: {}", fields.iter().map(|(f, _)| format!("{{{f}}}")).collect::<Vec<String>>().join(", ")))
+        }
+        rest = &rest[end+1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Works out where a download gets written, from the output directory, the -o filename or template, and the bulk index
+pub fn output_path(args: &Args, cobalt_filename: &str, bulk: u16) -> Result<std::path::PathBuf, String> {
+    let directory = match &args.output_dir {
+        Some(dir) => expand_home(dir),
+        None => std::env::current_dir().map_err(|e| e.to_string())?
+    };
+
+    let mut filename: std::path::PathBuf = match &args.out_filename {
+        Some(template) => {
+            let url = args.c_url.clone().unwrap();
+            let (title, ext) = cobalt_filename.rsplit_once('.').unwrap_or((cobalt_filename, ""));
+            let media_id = service::media_id(&url);
+            let media = if args.c_download_mode == tcargs::types::DownloadMode::Audio { "audio" } else { "video" };
+            // field values come from cobalt or the link, so they can't be allowed to add folders of their own,
+            // or to make up a . or .. folder that only the template itself may use
+            let fields: Vec<(&str, String)> = vec![
+                ("filename", cobalt_filename.to_string()),
+                ("title", title.to_string()),
+                ("ext", ext.to_string()),
+                ("service", service::detect(&url).map(|s| s.print()).unwrap_or("unknown".into())),
+                ("id", media_id.split_once(' ').map(|(_, id)| id.to_string()).unwrap_or(media_id.clone())),
+                ("index", bulk.to_string()),
+                ("type", media.to_string())
+            ].into_iter().map(|(field, value)| match value.chars().all(|c| c == '.') {
+                true => (field, value.replace('.', "_")),
+                false => (field, value.replace(['/', '\\'], "_"))
+            }).collect();
+            // a root or drive in the template is kept, so an absolute -o still goes where it points
+            let rendered = render_template(template, &fields)?.replace('\\', "/");
+            std::path::Path::new(&rendered).components().map(|part| match part {
+                std::path::Component::Normal(part) => sanitize_filename(&part.to_string_lossy()).into(),
+                part => part.as_os_str().to_os_string()
+            }).collect()
+        },
+        None => sanitize_filename(cobalt_filename).into()
+    };
+
+    let has_index = args.out_filename.as_ref().is_some_and(|t| t.contains("{index}"));
+    if bulk > 0 && !has_index {
+        // the number goes in front of the file itself, not in front of the directories in a template
+        if let Some(file) = filename.file_name().map(|file| file.to_string_lossy().to_string()) {
+            filename.set_file_name(format!("{bulk}_{file}"));
+        }
+    }
+    Ok(directory.join(filename))
+}
+
+/// Quotes a value so the shell passes it to the command as a single argument, whatever characters are in it
+pub fn shell_quote(value: &str) -> String {
+    if cfg!(target_os = "windows") {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// Runs the --exec command for a finished download through the system shell, with every field quoted
+pub async fn run_exec(args: &Args, path: &std::path::Path) -> Result<(), String> {
+    let template = args.exec.clone().unwrap();
+    let url = args.c_url.clone().unwrap();
+    let media = if args.c_download_mode == tcargs::types::DownloadMode::Audio { "audio" } else { "video" };
+    let command = render_template(&template, &[
+        ("path", shell_quote(&path.to_string_lossy())),
+        ("url", shell_quote(&url)),
+        ("service", shell_quote(&service::detect(&url).map(|s| s.print()).unwrap_or("unknown".into()))),
+        ("type", shell_quote(media))
+    ])?;
+
+    let mut process = if cfg!(target_os = "windows") {
+        let mut process = std::process::Command::new("cmd");
+        process.arg("/C").arg(&command);
+        process
+    } else {
+        let mut process = std::process::Command::new("sh");
+        process.arg("-c").arg(&command);
+        process
+    };
+    let status = tokio::task::spawn_blocking(move || process.status()).await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Unable to run the command \"{command}\": {e}"))?;
+    match status.code() {
+        Some(0) => Ok(()),
+        Some(code) => Err(format!("The command \"{command}\" exited with status {code}")),
+        None => Err(format!("The command \"{command}\" was stopped by a signal"))
+    }
+}
+
+/// Names that Windows refuses to create a file with, with or without an extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9"
+];
+/// Most filesystems limit a single file name to 255 bytes
+const MAX_FILENAME_BYTES: usize = 255;
+
+/// Makes a single file name safe to write on any OS. Path separators, control characters and characters Windows forbids
+/// are replaced with '_', reserved names get a '_' in front, and names that are too long are cut down while keeping the extension
+pub fn sanitize_filename(name: &str) -> String {
+    let mut clean: String = name.chars()
+        .map(|c| if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .collect();
+    // windows silently drops trailing dots and spaces
+    clean = clean.trim().trim_end_matches('.').to_string();
+    if clean.is_empty() {
+        clean = String::from("_");
+    }
+
+    let stem = clean.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved)) {
+        clean = format!("_{clean}");
+    }
+
+    if clean.len() > MAX_FILENAME_BYTES {
+        let ext = match clean.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() && ext.len() <= 16 => format!(".{ext}"),
+            _ => String::new()
+        };
+        let mut cut = MAX_FILENAME_BYTES - ext.len();
+        while !clean.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        clean = format!("{}{ext}", clean[..cut].trim_end());
+    }
+    clean
+}
+
+/// An output file claimed by `resolve_collision`
+#[derive(Debug)]
+pub struct Output {
+    pub path: std::path::PathBuf,
+    pub file: std::fs::File,
+    /// Whether the file was made for this download, so it can be removed again if the download fails
+    pub created: bool
+}
+
+/// Applies the --if-exists policy to an output path, creating the file (and its directory) right away so that
+/// downloads running at the same time can't pick the same name, or write over a file another one just created.
+/// Returns the file to write to, None if the download should be skipped, or an error if it should fail
+pub fn resolve_collision(path: std::path::PathBuf, policy: &tcargs::types::Collision) -> Result<Option<Output>, String> {
+    let shown = path.to_string_lossy().to_string();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Unable to create the output directory:\n\"{e}\"\n(when writing to {shown})"))?;
+    }
+    let create = |path: &std::path::Path| std::fs::OpenOptions::new().write(true).create_new(true).open(path);
+    let error = |e: std::io::Error| format!("Unable to create the output file:\n\"{e}\"\n(when writing to {shown})");
+    match create(&path) {
+        Ok(file) => return Ok(Some(Output { path, file, created: true })),
+        Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => return Err(error(e)),
+        Err(_) => ()
+    }
+    match policy {
+        tcargs::types::Collision::Overwrite => {
+            // truncated only when the data is written, so a failed download leaves the old file as it was
+            let file = std::fs::OpenOptions::new().write(true).open(&path).map_err(error)?;
+            Ok(Some(Output { path, file, created: false }))
+        },
+        tcargs::types::Collision::Skip => Ok(None),
+        tcargs::types::Collision::Fail => Err(format!("The file {shown} already exists")),
+        tcargs::types::Collision::Rename => {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let (stem, ext) = match name.rsplit_once('.') {
+                Some((stem, ext)) if !stem.is_empty() => (stem.to_string(), format!(".{ext}")),
+                _ => (name.clone(), String::new())
+            };
+            let mut n = 1;
+            loop {
+                let candidate = path.with_file_name(format!("{stem} ({n}){ext}"));
+                match create(&candidate) {
+                    Ok(file) => return Ok(Some(Output { path: candidate, file, created: true })),
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => n += 1,
+                    Err(e) => return Err(error(e))
+                }
+            }
+        }
+    }
+}
+
+/// A single item cobalt offers in a picker response
+#[derive(Debug, Clone, PartialEq)]
+pub struct PickerItem {
+    pub kind: String,
+    pub url: String,
+    pub thumb: Option<String>
+}
+pub fn picker_items(json: &HashMap<String, json::JsonValue>) -> Vec<PickerItem> {
+    json.get("picker")
+        .and_then(|p| p.get_array().ok())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|item| {
+            let item = item.get_object().ok()?;
+            Some(PickerItem {
+                kind: item.get("type").and_then(|t| t.get_str().ok()).unwrap_or("unknown".into()),
+                url: item.get("url")?.get_str().ok()?,
+                thumb: item.get("thumb").and_then(|t| t.get_str().ok())
+            })
+        })
+        .collect()
+}
+
+pub fn get_url(args: &Args, status: &str, json: &std::collections::HashMap<String, json::JsonValue>) -> Result<String, String> {
     let media = if args.c_download_mode == tcargs::types::DownloadMode::Audio {
         "audio"
     } else {
@@ -36,27 +278,129 @@ pub fn get_url(args: &Args, status: &str, json: &std::collections::HashMap<Strin
             urls
         };
 
-        let choice = if args.picker_choice == 0 {
+        if urls.is_empty() {
+            return Err(format!("Cobalt returned a picker without any {media}s in it"));
+        }
+        if args.stdout && args.picker_choice == 0 && urls.len() > 1 {
+            return Err(format!("Cobalt returned {} {media}s to pick from, choose one with -p to write it to stdout", urls.len()));
+        }
+        let choice = if args.picker_choice == 0 && args.stdout {
+            // the only item is taken without asking, since stdin and stdout belong to the pipe
+            1
+        } else if args.picker_choice == 0 {
+            // the prompt goes to stderr like every other status message, so it never ends up in the output
             loop {
                 let mut buf = String::new();
-                print!("Choose which {media} to download [1-{}] >> ", urls.len());
-                std::io::stdout().flush().unwrap();
+                eprint!("Choose which {media} to download [1-{}] >> ", urls.len());
+                std::io::stderr().flush().unwrap();
                 std::io::stdin().read_line(&mut buf).unwrap();
                 if let Ok(int) = buf.trim().parse::<u8>() {
-                    if int as usize <= urls.len() {
+                    if int >= 1 && int as usize <= urls.len() {
                         break int;
                     }
                 }
-                println!("Input must be an integer between 1 and {}", urls.len());
+                eprintln!("Input must be an integer between 1 and {}", urls.len());
             }
         } else {
             args.picker_choice
         };
 
-        urls.get((choice - 1) as usize).unwrap_or(&urls[0]).clone()
+        Ok(urls.get((choice - 1) as usize).unwrap_or(&urls[0]).clone())
     } else {
-        json.get("url").unwrap().get_str().unwrap()
+        Ok(json.get("url").unwrap().get_str().unwrap())
+    }
+}
+
+enum RequestError {
+    /// The instance itself failed, so another instance might succeed
+    Instance(String, String),
+    /// The request would fail on any instance
+    Fatal(String)
+}
+
+/// Sends the cobalt request for `args`, moving down the configured instance list on instance failures if failover is enabled.
+/// Returns the parsed response along with the instance that answered it.
+pub async fn request_cobalt(args: &Args, debug: bool) -> Result<(HashMap<String, json::JsonValue>, String), String> {
+    let download_url = args.c_url.clone().unwrap();
+    let detected = service::check_url(&download_url)?;
+    if debug { eprintln!("[DEBUG {download_url}] Detected service: {}", detected.map(|s| s.print()).unwrap_or("unknown".into())) };
+    for warning in service::flag_warnings(args, detected) {
+        eprintln!("Warning: {warning}");
+    }
+
+    let mut instances = vec![args.cobalt_instance.clone()];
+    if args.failover {
+        for instance in args.instance_list.iter() {
+            if !instances.contains(instance) {
+                instances.push(instance.clone());
+            }
+        }
     }
+
+    let mut skipped = 0;
+    for instance in instances.iter() {
+        match post_request(args, instance, debug).await {
+            Ok(json) => {
+                if skipped > 0 {
+                    eprintln!("Instance {instance} succeeded after skipping {skipped} instance(s) (when downloading from {download_url})");
+                }
+                return Ok((json, instance.clone()));
+            },
+            Err(RequestError::Instance(reason, message)) => {
+                if !args.failover {
+                    return Err(message);
+                }
+                eprintln!("Skipping instance {instance}: {reason} (when downloading from {download_url})");
+                skipped += 1;
+            },
+            Err(RequestError::Fatal(message)) => return Err(message)
+        }
+    }
+    Err(format!("All {} instances failed (when downloading from {download_url})", instances.len()))
+}
+
+async fn post_request(args: &Args, instance: &str, debug: bool) -> Result<HashMap<String, json::JsonValue>, RequestError> {
+    let download_url = args.c_url.clone().unwrap();
+    let request = reqwest::Client::new().post(format!("https://{instance}/"))
+        .header("User-Agent", &format!("tcobalt {}", crate::VERSION.trim()))
+        .header("Accept", "application/json")
+        .header("Content-Type", "application/json")
+        .body(cobalt_args(args));
+
+    if debug { eprintln!("[DEBUG {download_url}] Sending POST request to cobalt server {instance} ...") };
+    let res = match request.send().await {
+        Ok(res) => res,
+        Err(e) => return Err(RequestError::Instance(
+            format!("server did not respond ({e})"),
+            format!("Cobalt server did not respond:\n\"{e}\"\n(when downloading from {download_url})")
+        ))
+    };
+
+    let body = res.text().await.unwrap_or_default();
+    if debug { eprintln!("[DEBUG {download_url}] Response received, parsing json ...") };
+    let json = match json::parse(&body) {
+        Ok(json) => json,
+        Err(e) => return Err(RequestError::Instance(
+            format!("server returned improper JSON ({e})"),
+            print_json_error(e, body)
+        ))
+    };
+
+    if let Some(Ok(status)) = json.get("status").map(|s| s.get_str()) {
+        if status == "error" {
+            let code = json.get("error")
+                .and_then(|e| e.get_object().ok())
+                .and_then(|e| e.get("code").and_then(|c| c.get_str().ok()))
+                .unwrap_or("unknown".into());
+            let message = format!("Cobalt returned error: \"{code}\" (when downloading from {download_url})");
+            // errors about the link or the content itself will not be any different on another instance
+            if code.starts_with("error.api.link") || code.starts_with("error.api.content") {
+                return Err(RequestError::Fatal(message));
+            }
+            return Err(RequestError::Instance(format!("cobalt returned error \"{code}\""), message));
+        }
+    }
+    Ok(json)
 }
 
 const POST_TEMPLATE: &str = "{
@@ -91,14 +435,53 @@ pub fn cobalt_args(args_in: &Args) -> String {
         .replace("<bitrate>", &args_in.c_audio_bitrate.to_string())
 }
 
+/// Prints a status message to stdout, or to stderr when the media itself is being written to stdout
+#[macro_export]
+macro_rules! status {
+    ($args: expr, $($fmt:tt)*) => {{
+        if $args.stdout {
+            eprintln!($($fmt)*);
+        } else {
+            println!($($fmt)*);
+        }
+    }};
+}
+
+/// How a single download ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// The media was written, with its size in bytes
+    Success(u64),
+    /// The media was written and archived with its size in bytes, but the --exec command after it failed
+    CommandFailed(u64, String),
+    Skipped(String),
+    Failed(String)
+}
+impl Outcome {
+    /// Whether the download itself failed, so it needs to be retried
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Self::Failed(_))
+    }
+
+    /// Whether tcobalt should exit with an error for it
+    pub fn is_error(&self) -> bool {
+        matches!(self, Self::Failed(_) | Self::CommandFailed(..))
+    }
+}
+
+/// Prints the error and turns it into a failed outcome
+pub fn fail(message: String) -> Outcome {
+    eprintln!("{message}");
+    Outcome::Failed(message)
+}
+
 #[macro_export]
 macro_rules! attempt {
     ($try: expr, $error_msg_format: literal $(,$($extra:expr),*)?) => {{
         let result = $try;
         if result.is_err() {
             let e = result.unwrap_err().to_string();
-            eprintln!($error_msg_format, e $(,$($extra)*)?);
-            return false;
+            return $crate::process::fail(format!($error_msg_format, e $(,$($extra)*)?));
         }
         result.unwrap()
     }};
@@ -107,8 +490,7 @@ macro_rules! attempt {
         if result.is_err() {
             let e = result.unwrap_err().to_string();
             let diag = $error_string_generator;
-            eprintln!("{}", diag.to_string().replace("{}", &e));
-            return false;
+            return $crate::process::fail(diag.to_string().replace("{}", &e));
         }
         result.unwrap()
     }};
@@ -0,0 +1,200 @@
+use crate::args::Args;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Service {
+    Bilibili, Bluesky, Dailymotion, Facebook, Instagram, Loom, Ok, Pinterest, Reddit, Rutube,
+    Snapchat, SoundCloud, Streamable, TikTok, Tumblr, Twitch, Twitter, Vimeo, Vine, Vk, YouTube
+}
+impl Service {
+    pub fn print(&self) -> String {
+        format!("{self:?}").to_lowercase()
+    }
+}
+
+/// Domains for every service, matched against the host and all of its parent domains
+const DOMAINS: &[(&str, Service)] = &[
+    ("bilibili.com", Service::Bilibili), ("bilibili.tv", Service::Bilibili), ("b23.tv", Service::Bilibili),
+    ("bsky.app", Service::Bluesky),
+    ("dailymotion.com", Service::Dailymotion), ("dai.ly", Service::Dailymotion),
+    ("facebook.com", Service::Facebook), ("fb.watch", Service::Facebook),
+    ("instagram.com", Service::Instagram), ("ddinstagram.com", Service::Instagram),
+    ("loom.com", Service::Loom),
+    ("ok.ru", Service::Ok),
+    ("pinterest.com", Service::Pinterest), ("pin.it", Service::Pinterest),
+    ("reddit.com", Service::Reddit), ("redd.it", Service::Reddit),
+    ("rutube.ru", Service::Rutube),
+    ("snapchat.com", Service::Snapchat),
+    ("soundcloud.com", Service::SoundCloud),
+    ("streamable.com", Service::Streamable),
+    ("tiktok.com", Service::TikTok),
+    ("tumblr.com", Service::Tumblr),
+    ("twitch.tv", Service::Twitch),
+    ("twitter.com", Service::Twitter), ("x.com", Service::Twitter), ("vxtwitter.com", Service::Twitter), ("fixvx.com", Service::Twitter),
+    ("vimeo.com", Service::Vimeo),
+    ("vine.co", Service::Vine),
+    ("vk.com", Service::Vk), ("vk.ru", Service::Vk), ("vkvideo.ru", Service::Vk),
+    ("youtube.com", Service::YouTube), ("youtu.be", Service::YouTube), ("youtube-nocookie.com", Service::YouTube)
+];
+
+/// Gets the lowercase host of a url, without the port, login or "www."
+pub fn host(url: &str) -> Option<String> {
+    let rest = url.trim().split_once("://").map(|(_, rest)| rest).unwrap_or(url.trim());
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority.rsplit_once('@').map(|(_, host)| host).unwrap_or(authority);
+    let host = authority.split(':').next()?.trim_end_matches('.').to_lowercase();
+    let host = host.strip_prefix("www.").map(String::from).unwrap_or(host);
+    if host.is_empty() {
+        return None;
+    }
+    Some(host)
+}
+
+/// Finds the service a url belongs to, if tcobalt knows about it
+pub fn detect(url: &str) -> Option<Service> {
+    let host = host(url)?;
+    // regional domains like pinterest.ca or pinterest.co.uk
+    if host.starts_with("pinterest.") || host.contains(".pinterest.") {
+        return Some(Service::Pinterest);
+    }
+    DOMAINS.iter().find_map(|(domain, service)| {
+        (host == *domain || host.ends_with(&format!(".{domain}"))).then_some(*service)
+    })
+}
+
+/// Rejects urls that can't possibly be media cobalt supports, without contacting cobalt
+pub fn check_url(url: &str) -> Result<Option<Service>, String> {
+    let host = match host(url) {
+        Some(host) => host,
+        None => return Err(format!("The URL {url} does not have a host")),
+    };
+    if host == "localhost" || host.ends_with(".localhost") || host.starts_with('[') {
+        return Err(format!("The URL {url} points to a local address, which cobalt can't download from"));
+    }
+    if host.split('.').all(|part| part.parse::<u8>().is_ok()) {
+        return Err(format!("The URL {url} points to an IP address, which cobalt doesn't support"));
+    }
+    if !host.contains('.') {
+        return Err(format!("The URL {url} does not have a valid domain"));
+    }
+    Ok(detect(url))
+}
+
+/// The services a service-specific flag applies to, or an empty list if it applies to all of them
+pub fn flag_services(flag: &str) -> &'static [Service] {
+    match flag {
+        "--vcodec" | "--dublang" => &[Service::YouTube],
+        "--twitter-gif" => &[Service::Twitter],
+        "--tt-full-audio" | "--tt-h265" => &[Service::TikTok],
+        _ => &[]
+    }
+}
+
+/// Warns about every flag from the command line that won't apply to the url's service
+pub fn flag_warnings(args: &Args, service: Option<Service>) -> Vec<String> {
+    let url = args.c_url.clone().unwrap_or_default();
+    let service = match service {
+        Some(service) => service,
+        None => return vec![format!("{} isn't a service tcobalt knows about, cobalt will likely reject {url}", host(&url).unwrap_or(url.clone()))]
+    };
+    args.service_flags.iter().filter_map(|flag| {
+        let services = flag_services(flag);
+        if services.is_empty() || services.contains(&service) {
+            return None;
+        }
+        let names: Vec<String> = services.iter().map(|s| s.print()).collect();
+        Some(format!("{flag} only applies to {} links, so it will be ignored for {url} ({})", names.join("/"), service.print()))
+    }).collect()
+}
+
+/// Query parameters that only track where a link was shared from
+const TRACKING_PARAMS: &[&str] = &[
+    "si", "feature", "fbclid", "gclid", "igsh", "igshid", "ref", "ref_src", "ref_url", "share_id",
+    "is_from_webapp", "sender_device", "_r", "_t", "mibextid", "rdt", "context"
+];
+
+/// Rewrites a url into one canonical form per known service, so that the same media always ends up with the same url.
+/// Tracking parameters are stripped, mobile and alternate hosts are unified, and short links are expanded where possible
+pub fn canonicalize(url: &str) -> String {
+    let url = url.trim();
+    let host = match host(url) {
+        Some(host) => host,
+        None => return url.to_string()
+    };
+    let rest = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let rest = rest.split('#').next().unwrap_or(rest);
+    let (path, query) = match rest.find('/') {
+        Some(idx) => rest[idx..].split_once('?').unwrap_or((&rest[idx..], "")),
+        None => ("/", rest.split_once('?').map(|(_, q)| q).unwrap_or(""))
+    };
+    let params: Vec<(&str, &str)> = query.split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| p.split_once('=').unwrap_or((p, "")))
+        .collect();
+    let param = |key: &str| params.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string());
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    match detect(url) {
+        Some(Service::YouTube) => {
+            let id = if host == "youtu.be" {
+                segments.first().map(|s| s.to_string())
+            } else if segments.first() == Some(&"watch") {
+                param("v")
+            } else if let ["shorts" | "embed" | "live" | "v", id, ..] = segments.as_slice() {
+                Some(id.to_string())
+            } else {
+                None
+            };
+            match id {
+                Some(id) => format!("https://www.youtube.com/watch?v={id}"),
+                None => rebuild("www.youtube.com", path, &params)
+            }
+        },
+        Some(Service::Twitter) => format!("https://twitter.com{}", path.trim_end_matches('/')),
+        Some(Service::TikTok) => {
+            let host = if host.starts_with("vm.") || host.starts_with("vt.") { host } else { String::from("www.tiktok.com") };
+            format!("https://{host}{path}")
+        },
+        Some(Service::Instagram) => format!("https://www.instagram.com{path}"),
+        Some(Service::SoundCloud) => {
+            let host = if host == "on.soundcloud.com" { host } else { String::from("soundcloud.com") };
+            format!("https://{host}{}", path.trim_end_matches('/'))
+        },
+        Some(Service::Reddit) => {
+            let host = if host == "redd.it" { host } else { String::from("www.reddit.com") };
+            format!("https://{host}{path}")
+        },
+        _ => {
+            let host = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url)
+                .split(['/', '?', '#']).next().unwrap_or_default().to_lowercase();
+            rebuild(&host, path, &params)
+        }
+    }
+}
+
+/// A stable identifier for the media behind a url, like "youtube zn5sTDXSp8E".
+/// Services without a known id format use their canonical url instead
+pub fn media_id(url: &str) -> String {
+    let canonical = canonicalize(url);
+    let service = detect(&canonical);
+    let path = canonical.split_once("://").map(|(_, rest)| rest).unwrap_or(&canonical);
+    let segments: Vec<&str> = path.split(['/', '?']).collect();
+    let id = match service {
+        Some(Service::YouTube) => canonical.split_once("?v=").map(|(_, id)| id.to_string()),
+        Some(Service::Twitter) => segments.iter().position(|s| *s == "status").and_then(|i| segments.get(i + 1)).map(|s| s.to_string()),
+        Some(Service::TikTok) => segments.iter().position(|s| *s == "video" || *s == "photo").and_then(|i| segments.get(i + 1)).map(|s| s.to_string()),
+        _ => None
+    };
+    format!("{} {}", service.map(|s| s.print()).unwrap_or("unknown".into()), id.unwrap_or(canonical))
+}
+
+fn rebuild(host: &str, path: &str, params: &[(&str, &str)]) -> String {
+    let kept: Vec<String> = params.iter()
+        .filter(|(k, _)| !k.starts_with("utm_") && !TRACKING_PARAMS.contains(k))
+        .map(|(k, v)| if v.is_empty() { k.to_string() } else { format!("{k}={v}") })
+        .collect();
+    if kept.is_empty() {
+        format!("https://{host}{path}")
+    } else {
+        format!("https://{host}{path}?{}", kept.join("&"))
+    }
+}
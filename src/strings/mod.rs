@@ -33,6 +33,14 @@ pub fn get_str(module: &str, symbol: &str) -> String {
     remove_trailing_whitespace(string)
 }
 
+/// The services bundled with tcobalt, as pairs of cobalt's service name and a description
+pub fn supported_services() -> Vec<(String, String)> {
+    get_str("info", "supported").lines().filter_map(|line| {
+        let (name, description) = line.split_once('|')?;
+        Some((name.trim().to_string(), description.trim().to_string()))
+    }).collect()
+}
+
 pub fn remove_trailing_whitespace(string: impl ToString) -> String {
     let mut string = string.to_string();
     if string.len() == 0 { return string }
@@ -1,12 +1,52 @@
+/// Points TCOBALT_CONFIG at a file that is never there, so the tests see the built-in defaults instead of the config of whoever runs them.
+/// Everything that reads the config file has to call this first, which `test_args` does
+fn no_config() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| std::env::set_var(crate::args::config::CONFIG_VAR, std::env::temp_dir().join(format!("tcobalt_tests_{}.conf", std::process::id()))));
+}
+
+/// `Args::override_args` without the config file of whoever runs the tests
+fn test_args(args: &[&str]) -> crate::args::Args {
+    no_config();
+    crate::args::Args::override_args(args)
+}
+
+/// A file in the temp dir that no other test (or test run) writes to, removed again when it is dropped
+struct TempFile(std::path::PathBuf);
+
+impl TempFile {
+    fn new(name: &str, contents: &str) -> TempFile {
+        static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let file = TempFile(std::env::temp_dir().join(format!("tcobalt_{name}_{}_{count}.txt", std::process::id())));
+        file.write(contents);
+        file
+    }
+
+    fn write(&self, contents: &str) {
+        std::fs::write(&self.0, contents).unwrap();
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
 #[test]
 fn args_help() {
     use crate::args::*;
 
-    let help_args = Args::override_args(&["help"]).parse().unwrap();
-    let help_get_args = Args::override_args(&["help", "get"]).parse().unwrap();
-    let help_list_args = Args::override_args(&["help", "list"]).parse().unwrap();
-    let help_bulk_args = Args::override_args(&["help", "bulk"]).parse().unwrap();
-    let help_examples = Args::override_args(&["help", "examples"]).parse().unwrap();
+    let help_args = test_args(&["help"]).parse().unwrap();
+    let help_get_args = test_args(&["help", "get"]).parse().unwrap();
+    let help_list_args = test_args(&["help", "list"]).parse().unwrap();
+    let help_bulk_args = test_args(&["help", "bulk"]).parse().unwrap();
+    let help_examples = test_args(&["help", "examples"]).parse().unwrap();
 
     assert_eq!(help_args.help_flag, Some(types::Help::Help));
     assert_eq!(help_get_args.help_flag, Some(types::Help::Get));
@@ -20,12 +60,13 @@ fn args_get() {
     use crate::args::*;
     let url = "https://www.youtube.com/watch?v=zn5sTDXSp8E";
 
-    let args1 = Args::override_args(&["get", url]).parse().unwrap();
-    let args2 = Args::override_args(&["get", url, "--vcodec", "av1"]).parse().unwrap();
-    let args3 = Args::override_args(&["get", "--vquality", "1440", url]).parse().unwrap();
-    let args4 = Args::override_args(&["get", "-cq", "vp9", "720", url]).parse().unwrap();
-    let args5 = Args::override_args(&["get", url, "-af", "ogg", "--output", "foo.ogg"]).parse().unwrap();
-    let args6 = Args::override_args(&["get", url, "-gmo", "bar.gif"]).parse().unwrap();
+    let args1 = test_args(&["get", url]).parse().unwrap();
+    let args2 = test_args(&["get", url, "--vcodec", "av1"]).parse().unwrap();
+    let args3 = test_args(&["get", "--vquality", "1440", url]).parse().unwrap();
+    let args4 = test_args(&["get", "-cq", "vp9", "720", url]).parse().unwrap();
+    let args5 = test_args(&["get", url, "-af", "ogg", "--output", "foo.ogg"]).parse().unwrap();
+    let args6 = test_args(&["get", url, "-gmo", "bar.gif"]).parse().unwrap();
+    let args7 = test_args(&["get", url, "--failover"]).parse().unwrap();
 
     assert_eq!(args1.method, Some(types::Method::Get));
     assert_eq!(args1.c_url, Some(url.to_string()));
@@ -46,45 +87,46 @@ fn args_get() {
     assert_eq!(args6.c_twitter_gif, true);
     assert_eq!(args6.c_download_mode, types::DownloadMode::Mute);
     assert_eq!(args6.out_filename, Some("bar.gif".into()));
+    assert!(!args1.failover);
+    assert!(args7.failover);
 }
 
 #[test]
 fn args_get_incorrect() {
-    use crate::args::*;
     let url = "https://www.youtube.com/watch?v=zn5sTDXSp8E";
 
-    Args::override_args(&["get", url, url]).parse().unwrap_err();
-    Args::override_args(&["get", "-q", "1080"]).parse().unwrap_err();
-    Args::override_args(&["get", "-q", "1081", url]).parse().unwrap_err();
-    Args::override_args(&["get", "-q", url]).parse().unwrap_err();
-    Args::override_args(&["get", url, "-af"]).parse().unwrap_err();
-    Args::override_args(&["get", url, "-cafamgo"]).parse().unwrap_err();
-    Args::override_args(&["get"]).parse().unwrap_err();
+    test_args(&["get", url, url]).parse().unwrap_err();
+    test_args(&["get", "-q", "1080"]).parse().unwrap_err();
+    test_args(&["get", "-q", "1081", url]).parse().unwrap_err();
+    test_args(&["get", "-q", url]).parse().unwrap_err();
+    test_args(&["get", url, "-af"]).parse().unwrap_err();
+    test_args(&["get", url, "-cafamgo"]).parse().unwrap_err();
+    test_args(&["get"]).parse().unwrap_err();
 }
 
 #[test]
 fn args_bulk_get() {
-    use crate::args::*;
     let url1 = "https://www.youtube.com/watch?v=zn5sTDXSp8E";
     let url2 = "https://www.youtube.com/watch?v=OnrbdAAokS0";
 
-    let bulk1 = Args::override_args(&["bulk", "get", url2, url1,]).parse().unwrap();
-    let mut dummy_get_1 = Args::override_args(&["get", "https://"]).parse().unwrap();
+    let bulk1 = test_args(&["bulk", "get", url2, url1,]).parse().unwrap();
+    let mut dummy_get_1 = test_args(&["get", "https://"]).parse().unwrap();
     dummy_get_1.c_url = Some(url1.into());
-    let mut dummy_get_2 = Args::override_args(&["get", "https://"]).parse().unwrap();
+    let mut dummy_get_2 = test_args(&["get", "https://"]).parse().unwrap();
     dummy_get_2.c_url = Some(url2.into());
     eprintln!("{:#?}", bulk1.bulk_array.clone().unwrap());
-    assert_eq!(bulk1.bulk_array.clone().unwrap()[0], dummy_get_1);
-    assert_eq!(bulk1.bulk_array.unwrap()[1], dummy_get_2);
+    // the links keep the order they were given in
+    assert_eq!(bulk1.bulk_array.clone().unwrap()[0], dummy_get_2);
+    assert_eq!(bulk1.bulk_array.unwrap()[1], dummy_get_1);
     
-    let bulk2 = Args::override_args(&["bulk", "get", url2, "-cqm", "av1", "1440", url1,]).parse().unwrap();
-    let mut dummy_get_1 = Args::override_args(&["get", "https://", "-cqm", "av1", "1440"]).parse().unwrap();
+    let bulk2 = test_args(&["bulk", "get", url2, "-cqm", "av1", "1440", url1,]).parse().unwrap();
+    let mut dummy_get_1 = test_args(&["get", "https://", "-cqm", "av1", "1440"]).parse().unwrap();
     dummy_get_1.c_url = Some(url1.into());
-    let mut dummy_get_2 = Args::override_args(&["get", "https://", "-cqm", "av1", "1440"]).parse().unwrap();
+    let mut dummy_get_2 = test_args(&["get", "https://", "-cqm", "av1", "1440"]).parse().unwrap();
     dummy_get_2.c_url = Some(url2.into());
     eprintln!("{:#?}", bulk2.bulk_array.clone().unwrap());
-    assert_eq!(bulk2.bulk_array.clone().unwrap()[0], dummy_get_1);
-    assert_eq!(bulk2.bulk_array.unwrap()[1], dummy_get_2);
+    assert_eq!(bulk2.bulk_array.clone().unwrap()[0], dummy_get_2);
+    assert_eq!(bulk2.bulk_array.unwrap()[1], dummy_get_1);
 }
 
 #[test]
@@ -231,6 +273,26 @@ fn empty_json() {
     assert_eq!(proper_aeo, parsed_aeo);
 }
 
+#[test]
+fn json_sibling_objects() {
+    use std::collections::HashMap;
+    use crate::json::{self, JsonValue as Val};
+
+    let parsed = json::parse("{\"a\":{\"b\":{\"c\":1},\"d\":[2]},\"e\":[{\"f\":{}},{}],\"g\":null}").unwrap();
+    let proper: HashMap<String, Val> = HashMap::from([
+        ("a".into(), Val::Object(HashMap::from([
+            ("b".into(), Val::Object(HashMap::from([("c".into(), Val::Int(1))]))),
+            ("d".into(), Val::Array(vec![Val::Int(2)]))
+        ]))),
+        ("e".into(), Val::Array(vec![
+            Val::Object(HashMap::from([("f".into(), Val::Object(HashMap::new()))])),
+            Val::Object(HashMap::new())
+        ])),
+        ("g".into(), Val::Null)
+    ]);
+    assert_eq!(proper, parsed);
+}
+
 #[test]
 fn incorrect_json() {
     use crate::json;
@@ -261,3 +323,752 @@ fn incorrect_json() {
     assert!(json::parse(colon_in_array).is_err());
     assert!(json::parse(two_colons).is_err());
 }
+
+#[test]
+fn instances_rank() {
+    use crate::args::*;
+    use crate::instance::{self, HealthReport, InstanceInfo};
+    use std::time::Duration;
+
+    let args = test_args(&["instances", "--json", "-r"]).parse().unwrap();
+    assert_eq!(args.method, Some(types::Method::Instances));
+    assert!(args.json_output);
+    assert!(args.reorder_instances);
+    test_args(&["instances", "--foo"]).parse().unwrap_err();
+
+    let info = InstanceInfo {
+        version: Some("10.0.0".into()),
+        services: vec!["youtube".into()],
+        ..Default::default()
+    };
+    let report = |instance: &str, ms: u64, up: bool| HealthReport {
+        instance: instance.into(),
+        latency: Duration::from_millis(ms),
+        info: if up { Ok(info.clone()) } else { Err("did not respond".into()) }
+    };
+    let mut reports = vec![report("down1", 5, false), report("slow", 300, true), report("down2", 1, false), report("fast", 20, true)];
+    instance::rank(&mut reports);
+    let order: Vec<&str> = reports.iter().map(|r| r.instance.as_str()).collect();
+    assert_eq!(order, vec!["fast", "slow", "down1", "down2"]);
+}
+
+#[test]
+fn instance_info_schemas() {
+    use crate::json;
+    use crate::instance::InstanceInfo;
+
+    let current = json::parse("{\"cobalt\":{\"version\":\"10.1.0\",\"url\":\"https://api.example.com/\",\"startTime\":\"1726000000000\",\"durationLimit\":10800,\"turnstileSitekey\":\"0x4AAA\",\"services\":[\"youtube\",\"tiktok\"]},\"git\":{\"branch\":\"main\",\"commit\":\"abcdef0\"}}").unwrap();
+    let info = InstanceInfo::from_json(&current);
+    assert_eq!(info.version, Some("10.1.0".into()));
+    assert_eq!(info.branch, Some("main".into()));
+    assert_eq!(info.start_time, Some(1726000000000));
+    assert_eq!(info.duration_limit, Some(10800));
+    assert_eq!(info.services, vec!["youtube".to_string(), "tiktok".to_string()]);
+    assert!(info.turnstile);
+
+    let missing = json::parse("{\"cobalt\":{\"version\":\"10.0.0\"}}").unwrap();
+    let info = InstanceInfo::from_json(&missing);
+    assert_eq!(info.version, Some("10.0.0".into()));
+    assert_eq!(info.commit, None);
+    assert!(info.services.is_empty());
+    assert!(!info.turnstile);
+
+    let old = json::parse("{\"version\":\"7.15\",\"commit\":\"1234567\",\"branch\":\"current\",\"startTime\":1700000000000}").unwrap();
+    let info = InstanceInfo::from_json(&old);
+    assert_eq!(info.version, Some("7.15".into()));
+    assert_eq!(info.commit, Some("1234567".into()));
+    assert_eq!(info.start_time, Some(1700000000000));
+}
+
+#[test]
+fn services_compare() {
+    use crate::instance;
+
+    let services: Vec<String> = ["youtube", "TikTok", "twitch clips", "xiaohongshu"].iter().map(|s| s.to_string()).collect();
+    let lines = instance::compare_services(&services);
+    assert!(lines.contains(&(' ', "youtube videos, shorts & music".to_string())));
+    assert!(lines.contains(&(' ', "tiktok videos, photos & audio".to_string())));
+    assert!(lines.contains(&(' ', "twitch clips".to_string())));
+    assert!(lines.contains(&('-', "vine archive".to_string())));
+    assert_eq!(lines.last(), Some(&('+', "xiaohongshu".to_string())));
+}
+
+#[test]
+fn service_detection() {
+    use crate::service::{self, Service};
+
+    assert_eq!(service::detect("https://youtu.be/zn5sTDXSp8E"), Some(Service::YouTube));
+    assert_eq!(service::detect("https://m.youtube.com/watch?v=zn5sTDXSp8E"), Some(Service::YouTube));
+    assert_eq!(service::detect("https://vm.tiktok.com/ZM2abc/"), Some(Service::TikTok));
+    assert_eq!(service::detect("https://x.com/user/status/1"), Some(Service::Twitter));
+    assert_eq!(service::detect("https://www.instagram.com/reel/abc/"), Some(Service::Instagram));
+    assert_eq!(service::detect("https://soundcloud.com/artist/song"), Some(Service::SoundCloud));
+    assert_eq!(service::detect("https://pinterest.co.uk/pin/1/"), Some(Service::Pinterest));
+    assert_eq!(service::detect("https://notyoutube.com/watch?v=1"), None);
+    assert_eq!(service::detect("https://example.com/video.mp4"), None);
+
+    assert!(service::check_url("https://").is_err());
+    assert!(service::check_url("https://localhost:8080/video").is_err());
+    assert!(service::check_url("https://192.168.1.1/video").is_err());
+    assert!(service::check_url("https://intranet/video").is_err());
+    assert_eq!(service::check_url("https://example.com/video"), Ok(None));
+
+    let url = "https://www.youtube.com/watch?v=zn5sTDXSp8E";
+    let args = test_args(&["get", url, "-hc", "av1", "--twitter-gif", "-a"]).parse().unwrap();
+    assert_eq!(args.service_flags, vec!["--tt-h265".to_string(), "--vcodec".to_string(), "--twitter-gif".to_string()]);
+    let warnings = service::flag_warnings(&args, Some(Service::YouTube));
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings[0].starts_with("--tt-h265 only applies to tiktok links"));
+    assert!(service::flag_warnings(&args, Some(Service::Twitter)).iter().any(|w| w.starts_with("--vcodec")));
+}
+
+#[test]
+fn url_canonicalize_dedup() {
+    use crate::service;
+
+    let canonical = "https://www.youtube.com/watch?v=zn5sTDXSp8E";
+    assert_eq!(service::canonicalize("https://youtu.be/zn5sTDXSp8E?si=abc"), canonical);
+    assert_eq!(service::canonicalize("https://www.youtube.com/watch?v=zn5sTDXSp8E&t=30"), canonical);
+    assert_eq!(service::canonicalize("https://m.youtube.com/watch?feature=share&v=zn5sTDXSp8E"), canonical);
+    assert_eq!(service::canonicalize("https://youtube.com/shorts/zn5sTDXSp8E"), canonical);
+    assert_eq!(service::canonicalize("https://x.com/user/status/123?s=20#m"), "https://twitter.com/user/status/123");
+    assert_eq!(service::canonicalize("https://www.tiktok.com/@user/video/1?is_from_webapp=1&_r=1"), "https://www.tiktok.com/@user/video/1");
+    assert_eq!(service::canonicalize("https://m.soundcloud.com/artist/song/?si=1&utm_source=x"), "https://soundcloud.com/artist/song");
+    assert_eq!(service::canonicalize("https://vimeo.com/123?utm_source=x&h=abc"), "https://vimeo.com/123?h=abc");
+
+    let short = "https://youtu.be/zn5sTDXSp8E";
+    let timed = "https://www.youtube.com/watch?v=zn5sTDXSp8E&t=30";
+    let bulk = test_args(&["bulk", "get", short, timed, "-a"]).parse().unwrap();
+    // the canonical url is only compared, the first link is kept and goes to cobalt as it was given
+    assert_eq!(bulk.bulk_array.unwrap(), vec![{
+        let mut args = test_args(&["get", "https://", "-a"]).parse().unwrap();
+        args.c_url = Some(short.into());
+        args
+    }]);
+    assert_eq!(bulk.warnings.len(), 1);
+    let music = "https://music.youtube.com/watch?v=zn5sTDXSp8E";
+    let bulk = test_args(&["bulk", "get", music, "-a"]).parse().unwrap();
+    assert_eq!(bulk.bulk_array.unwrap()[0].c_url, Some(music.into()));
+
+    let file = TempFile::new("dedup", &format!("{short} -o 1.mp4\n{timed} -o 1.mp4\n{canonical} -a\n"));
+    let bulk = test_args(&["bulk", "execute", file.path()]).parse().unwrap();
+    assert_eq!(bulk.bulk_array.unwrap().len(), 2);
+    assert_eq!(bulk.warnings, vec![format!("Skipping line 2 ({timed}): it downloads the same media as line 1 ({short})")]);
+    assert_eq!(bulk.bulk_duplicates, vec![(String::from(timed), format!("duplicate of line 1 ({short})"))]);
+}
+
+#[test]
+fn download_archive() {
+    use crate::archive;
+
+    let file = TempFile::new("Archive", "");
+    let path_str = file.path();
+
+    let args = test_args(&["get", "https://youtu.be/zn5sTDXSp8E?si=abc", "--archive", path_str]).parse().unwrap();
+    let same = test_args(&["get", "https://www.youtube.com/watch?v=zn5sTDXSp8E", "--archive", path_str]).parse().unwrap();
+    let audio = test_args(&["get", "https://www.youtube.com/watch?v=zn5sTDXSp8E", "-a"]).parse().unwrap();
+    let forced = test_args(&["get", "https://youtu.be/zn5sTDXSp8E", "--archive", path_str, "--force"]).parse().unwrap();
+    assert_eq!(args.archive, Some(path_str.to_string()));
+    assert!(forced.force);
+
+    let entry = archive::entry(&args);
+    assert!(entry.starts_with("youtube zn5sTDXSp8E mode=auto,"));
+    assert_eq!(entry, archive::entry(&same));
+    assert_ne!(entry, archive::entry(&audio));
+
+    assert!(!archive::contains(path_str, &entry));
+    archive::record(path_str, &entry).unwrap();
+    assert!(archive::contains(path_str, &archive::entry(&same)));
+    assert!(!archive::contains(path_str, &archive::entry(&audio)));
+}
+
+#[test]
+fn output_templates() {
+    use crate::process::output_path;
+
+    let url = "https://twitter.com/user/status/1234567890";
+    let args = test_args(&["get", url, "-d", "/tmp/Videos", "-o", "{service}/{title}.{ext}"]).parse().unwrap();
+    assert_eq!(args.output_dir, Some(String::from("/tmp/Videos")));
+    assert_eq!(args.out_filename, Some(String::from("{service}/{title}.{ext}")));
+    assert_eq!(output_path(&args, "twitter_1234567890.mp4", 0).unwrap(), std::path::PathBuf::from("/tmp/Videos/twitter/twitter_1234567890.mp4"));
+    assert_eq!(output_path(&args, "twitter_1234567890.mp4", 3).unwrap(), std::path::PathBuf::from("/tmp/Videos/twitter/3_twitter_1234567890.mp4"));
+
+    let args = test_args(&["get", url, "-ao", "{index}_{id}-{type}.{ext}"]).parse().unwrap();
+    let path = output_path(&args, "cobalt.mp3", 2).unwrap();
+    assert_eq!(path, std::env::current_dir().unwrap().join("2_1234567890-audio.mp3"));
+
+    let args = test_args(&["get", url, "-o", "Plain.MP4"]).parse().unwrap();
+    assert_eq!(output_path(&args, "cobalt.mp4", 0).unwrap(), std::env::current_dir().unwrap().join("Plain.MP4"));
+
+    // an absolute -o keeps its root and goes where it points, whatever the output directory is
+    let args = test_args(&["get", url, "-d", "/tmp/Videos", "-o", "/tmp/x.mp4"]).parse().unwrap();
+    assert_eq!(output_path(&args, "cobalt.mp4", 0).unwrap(), std::path::PathBuf::from("/tmp/x.mp4"));
+    assert_eq!(output_path(&args, "cobalt.mp4", 4).unwrap(), std::path::PathBuf::from("/tmp/4_x.mp4"));
+    let args = test_args(&["get", url, "-d", "/tmp/Videos", "-o", "clips/{id}.{ext}"]).parse().unwrap();
+    assert_eq!(output_path(&args, "cobalt.mp4", 0).unwrap(), std::path::PathBuf::from("/tmp/Videos/clips/1234567890.mp4"));
+
+    // a title of .. stays inside the output directory, while .. in the template itself is kept
+    let args = test_args(&["get", url, "-d", "/tmp/Videos/new", "-o", "{title}/x.{ext}"]).parse().unwrap();
+    assert_eq!(output_path(&args, "...mp4", 0).unwrap(), std::path::PathBuf::from("/tmp/Videos/new/__/x.mp4"));
+    let args = test_args(&["get", url, "-d", "/tmp/Videos/new", "-o", "{title}{title}/x.{ext}"]).parse().unwrap();
+    assert_eq!(output_path(&args, "..mp4", 0).unwrap(), std::path::PathBuf::from("/tmp/Videos/new/__/x.mp4"));
+    let args = test_args(&["get", url, "-d", "/tmp/Videos/new", "-o", "../{title}.{ext}"]).parse().unwrap();
+    assert_eq!(output_path(&args, "clip.mp4", 0).unwrap(), std::path::PathBuf::from("/tmp/Videos/new/../clip.mp4"));
+
+    assert!(test_args(&["get", url, "-o", "{nope}.mp4"]).parse().is_err());
+    assert!(test_args(&["get", url, "-o", "{title.mp4"]).parse().is_err());
+}
+
+#[test]
+fn filename_safety() {
+    use crate::args::*;
+    use crate::process::{output_path, resolve_collision, sanitize_filename};
+
+    assert_eq!(sanitize_filename("../../etc/passwd"), ".._.._etc_passwd");
+    assert_eq!(sanitize_filename("what? a \"video\": 1|2.mp4"), "what_ a _video__ 1_2.mp4");
+    assert_eq!(sanitize_filename("tab\there\n.mp4"), "tab_here_.mp4");
+    assert_eq!(sanitize_filename("CON.mp4"), "_CON.mp4");
+    assert_eq!(sanitize_filename("lpt1"), "_lpt1");
+    assert_eq!(sanitize_filename("console.mp4"), "console.mp4");
+    assert_eq!(sanitize_filename(".."), "_");
+    assert_eq!(sanitize_filename("trailing. "), "trailing");
+    let long = sanitize_filename(&format!("{}.webm", "é".repeat(200)));
+    assert!(long.len() <= 255);
+    assert!(long.ends_with("é.webm"));
+
+    let url = "https://twitter.com/user/status/1234567890";
+    let args = test_args(&["get", url, "-d", "/tmp", "-o", "{service}/{title}.{ext}"]).parse().unwrap();
+    assert_eq!(output_path(&args, "../../evil/name.mp4", 0).unwrap(), std::path::PathBuf::from("/tmp/twitter/.._.._evil_name.mp4"));
+    let args = test_args(&["get", url, "-d", "/tmp"]).parse().unwrap();
+    assert_eq!(args.if_exists, types::Collision::Rename);
+    assert_eq!(output_path(&args, "a/b.mp4", 0).unwrap(), std::path::PathBuf::from("/tmp/a_b.mp4"));
+
+    let dir = std::env::temp_dir().join(format!("tcobalt_collision_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let taken = dir.join("video.mp4");
+    std::fs::write(&taken, "").unwrap();
+    std::fs::write(dir.join("video (1).mp4"), "").unwrap();
+    let resolve = |path: &std::path::Path, policy| resolve_collision(path.to_path_buf(), &policy).map(|o| o.map(|o| (o.path, o.created)));
+    assert_eq!(resolve(&dir.join("sub/new.mp4"), types::Collision::Fail).unwrap(), Some((dir.join("sub/new.mp4"), true)));
+    // the file is created right away, so the next download to the same path sees it
+    assert!(resolve(&dir.join("sub/new.mp4"), types::Collision::Fail).is_err());
+    assert_eq!(resolve(&taken, types::Collision::Overwrite).unwrap(), Some((taken.clone(), false)));
+    assert_eq!(resolve(&taken, types::Collision::Skip).unwrap(), None);
+    assert!(resolve(&taken, types::Collision::Fail).is_err());
+    assert_eq!(resolve(&taken, types::Collision::Rename).unwrap(), Some((dir.join("video (2).mp4"), true)));
+    assert_eq!(resolve(&taken, types::Collision::Rename).unwrap(), Some((dir.join("video (3).mp4"), true)));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let args = test_args(&["get", url, "--if-exists", "SKIP"]).parse().unwrap();
+    assert_eq!(args.if_exists, types::Collision::Skip);
+    assert!(test_args(&["get", url, "--if-exists", "explode"]).parse().is_err());
+}
+
+#[test]
+fn output_stdout() {
+    let url = "https://www.youtube.com/watch?v=zn5sTDXSp8E";
+    let args = test_args(&["get", url, "-o", "-"]).parse().unwrap();
+    assert!(args.stdout);
+    assert_eq!(args.out_filename, None);
+    assert!(!test_args(&["get", url]).parse().unwrap().stdout);
+
+    assert!(test_args(&["bulk", "get", url, "-o", "-"]).parse().is_err());
+    let file = TempFile::new("stdout", &format!("{url} -o -\n"));
+    let bulk = test_args(&["bulk", "execute", file.path()]).parse();
+    assert!(bulk.is_err());
+
+    // a lone picker item is taken without asking, since stdin and stdout belong to the pipe
+    let picker = |items: &str| crate::json::parse(format!("{{\"status\":\"picker\",\"picker\":[{items}]}}")).unwrap();
+    let item = "{\"type\":\"video\",\"url\":\"https://example.com/1.mp4\"}";
+    assert_eq!(crate::process::get_url(&args, "picker", &picker(item)).unwrap(), "https://example.com/1.mp4");
+    assert!(crate::process::get_url(&args, "picker", &picker(&format!("{item},{item}"))).unwrap_err().contains("choose one with -p"));
+    assert!(crate::process::get_url(&args, "picker", &picker("")).is_err());
+}
+
+#[test]
+fn url_method() {
+    use crate::args::*;
+
+    let url = "https://www.youtube.com/watch?v=zn5sTDXSp8E";
+    let args = test_args(&["url", url, "-aq", "720", "--json"]).parse().unwrap();
+    assert_eq!(args.method, Some(types::Method::Url));
+    assert_eq!(args.c_download_mode, types::DownloadMode::Audio);
+    assert!(args.json_output);
+    let short = test_args(&["u", url, "-p", "2"]).parse().unwrap();
+    assert_eq!(short.method, Some(types::Method::Url));
+    assert_eq!(short.picker_choice, 2);
+    assert!(test_args(&["get", url, "--json"]).parse().is_err());
+    let info = test_args(&["info", url, "-m"]).parse().unwrap();
+    assert_eq!(info.method, Some(types::Method::Info));
+    assert_eq!(info.c_download_mode, types::DownloadMode::Mute);
+    assert!(test_args(&["info", url, "--json"]).parse().is_err());
+    assert_eq!(test_args(&["help", "url"]).parse().unwrap().help_flag, Some(types::Help::Url));
+
+    let json = crate::json::parse(r#"{"status": "picker", "picker": [
+        {"type": "photo", "url": "https://a.example/1.jpg"},
+        {"type": "video", "url": "https://a.example/2.mp4", "thumb": "https://a.example/2.jpg"}
+    ]}"#).unwrap();
+    let items = crate::process::picker_items(&json);
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].kind, "photo");
+    assert_eq!(items[0].thumb, None);
+    assert_eq!(items[1].url, "https://a.example/2.mp4");
+    assert_eq!(items[1].thumb, Some(String::from("https://a.example/2.jpg")));
+}
+
+#[test]
+fn dry_run() {
+    let url = "https://www.youtube.com/watch?v=zn5sTDXSp8E";
+    assert!(test_args(&["get", url, "--dry-run"]).parse().unwrap().dry_run);
+    // url and info always contact cobalt, so they don't take the option at all
+    assert!(test_args(&["url", url, "--dry-run"]).parse().is_err());
+    assert!(test_args(&["info", url, "--dry-run"]).parse().is_err());
+    assert!(!crate::completions::fish().contains("from url u' -l dry-run"));
+    assert!(crate::completions::fish().contains("from get g' -l dry-run"));
+    assert!(!test_args(&["get", url]).parse().unwrap().dry_run);
+
+    let bulk = test_args(&["bulk", "get", url, "https://youtu.be/OnrbdAAokS0", "--dry-run"]).parse().unwrap();
+    assert!(bulk.dry_run);
+    assert!(bulk.bulk_array.unwrap().iter().all(|a| a.dry_run));
+
+    let file = TempFile::new("dry_run", &format!("{url} -a\nhttps://youtu.be/OnrbdAAokS0\n"));
+    let path_str = file.path();
+    let bulk = test_args(&["bulk", "execute", path_str, "--dry-run"]).parse().unwrap();
+    let plain = test_args(&["bulk", "execute", path_str]).parse().unwrap();
+    let invalid = test_args(&["bulk", "execute", path_str, "--nope"]).parse();
+    assert!(bulk.bulk_array.unwrap().iter().all(|a| a.dry_run));
+    assert!(plain.bulk_array.unwrap().iter().all(|a| !a.dry_run));
+    assert!(invalid.is_err());
+}
+
+#[test]
+fn exec_hook() {
+    use crate::process::{run_exec, shell_quote};
+
+    let url = "https://twitter.com/user/status/1234567890";
+    let args = test_args(&["get", url, "--exec", "echo {path} {url} > {path}.txt"]).parse().unwrap();
+    assert_eq!(args.exec, Some(String::from("echo {path} {url} > {path}.txt")));
+    assert!(test_args(&["get", url, "--exec", "echo {filename}"]).parse().is_err());
+
+    if cfg!(unix) {
+        assert_eq!(shell_quote("it's here"), "'it'\\''s here'");
+        let path = std::env::temp_dir().join(format!("tcobalt exec 'test' {}.mp4", std::process::id()));
+        let args = test_args(&["get", url, "-a", "--exec", "printf '%s|%s|%s' {service} {type} {path} > {path}.txt"]).parse().unwrap();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(run_exec(&args, &path)).unwrap();
+        let written = path.with_extension("mp4.txt");
+        assert_eq!(std::fs::read_to_string(&written).unwrap(), format!("twitter|audio|{}", path.to_string_lossy()));
+        std::fs::remove_file(&written).unwrap();
+
+        let args = test_args(&["get", url, "--exec", "exit 3"]).parse().unwrap();
+        assert!(rt.block_on(run_exec(&args, &path)).unwrap_err().contains("exited with status 3"));
+    }
+}
+
+#[test]
+fn bulk_retry_lines() {
+    let first = "https://www.youtube.com/watch?v=zn5sTDXSp8E";
+    let second = "https://youtu.be/OnrbdAAokS0";
+    let bulk = test_args(&["bulk", "get", first, second, "-a", "--retry-file", "Retry.txt"]).parse().unwrap();
+    assert_eq!(bulk.retry_file, Some(String::from("Retry.txt")));
+    assert_eq!(bulk.bulk_array.as_ref().unwrap().len(), bulk.bulk_lines.len());
+    assert!(bulk.bulk_lines.contains(&format!("{first} -a")));
+    assert!(bulk.bulk_array.unwrap().iter().all(|a| a.retry_file.is_none()));
+    assert!(test_args(&["bulk", "get", first, "--retry-file"]).parse().is_err());
+
+    let file = TempFile::new("retry", &format!("{first} -a\n{second}  \n{first}&si=x -a\n"));
+    let bulk = test_args(&["bulk", "execute", file.path(), "--retry-file", "/tmp/retry.txt", "--dry-run"]).parse().unwrap();
+    assert_eq!(bulk.retry_file, Some(String::from("/tmp/retry.txt")));
+    assert!(bulk.dry_run);
+    // the duplicate third line is dropped along with its source line
+    assert_eq!(bulk.bulk_lines, vec![format!("{first} -a"), String::from(second)]);
+
+    let outcome = crate::process::Outcome::Failed(String::from("error"));
+    assert!(outcome.is_failed());
+    assert!(!crate::process::Outcome::Skipped(String::from("dry run")).is_failed());
+    // a failed --exec command still exits with an error, but the download isn't retried
+    let command = crate::process::Outcome::CommandFailed(10, String::from("exited with status 3"));
+    assert!(command.is_error() && !command.is_failed());
+}
+
+#[test]
+fn bulk_file_syntax() {
+    use crate::args::bulk::{tokenize, parse_file, quote};
+    no_config();
+
+    let owned = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+    assert_eq!(tokenize("  a   b\tc  ").unwrap(), owned(&["a", "b", "c"]));
+    assert_eq!(tokenize("-o \"my video.mp4\" x").unwrap(), owned(&["-o", "my video.mp4", "x"]));
+    assert_eq!(tokenize("'it''s' \"a \\\"b\\\" \\n\"").unwrap(), owned(&["its", "a \"b\" \\n"]));
+    assert_eq!(tokenize("my\\ file.mp4 \"\"").unwrap(), owned(&["my file.mp4", ""]));
+    assert_eq!(tokenize("https://x.com/a#frag # comment").unwrap(), owned(&["https://x.com/a#frag"]));
+    assert_eq!(tokenize("   # just a comment").unwrap(), owned(&[]));
+    assert_eq!(tokenize("-o \"unclosed").unwrap_err().0, 4);
+    assert_eq!(tokenize("a 'b").unwrap_err().0, 3);
+    assert_eq!(tokenize("trailing\\").unwrap_err().0, 9);
+    for arg in ["plain.mp4", "with space.mp4", "it's", "", "#hash", "back\\slash", "${HOME}"] {
+        assert_eq!(tokenize(&quote(arg)).unwrap(), vec![arg.to_string()]);
+    }
+
+    let lines = parse_file("# header\n\nhttps://youtu.be/a -a  \n  \t\nhttps://youtu.be/b -o 'b c.mp4' # note\n").unwrap();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].number, 3);
+    assert_eq!(lines[0].text, "https://youtu.be/a -a");
+    assert_eq!(lines[1].number, 5);
+    assert_eq!(lines[1].tokens, owned(&["https://youtu.be/b", "-o", "b c.mp4"]));
+    let error = parse_file("https://youtu.be/a\nhttps://youtu.be/b -o \"x.mp4").unwrap_err().print();
+    assert!(error.contains("On line 2, column 23"));
+    assert!(error.contains("https://youtu.be/b -o \"x.mp4\n"));
+
+    let file = TempFile::new("bulk_syntax", "# comment\n\nhttps://youtu.be/zn5sTDXSp8E  -o \"rice video.mp4\"\n");
+    let path_str = file.path();
+    let bulk = test_args(&["bulk", "execute", path_str]).parse().unwrap();
+    let array = bulk.bulk_array.unwrap();
+    assert_eq!(array.len(), 1);
+    assert_eq!(array[0].out_filename, Some(String::from("rice video.mp4")));
+    assert_eq!(bulk.bulk_lines, vec![String::from("https://youtu.be/zn5sTDXSp8E  -o \"rice video.mp4\"")]);
+    file.write("https://youtu.be/zn5sTDXSp8E\n\nhttps://youtu.be/OnrbdAAokS0 --bogus\n");
+    let error = test_args(&["bulk", "execute", path_str]).parse().unwrap_err().print();
+    assert!(error.contains("On line 3, column 30"));
+}
+
+#[test]
+fn bulk_file_directives() {
+    use crate::args::bulk::{tokenize, parse_file};
+    no_config();
+
+    std::env::set_var("TCOBALT_TEST_DIR", "/tmp/my videos");
+    std::env::remove_var("TCOBALT_TEST_UNSET");
+    assert_eq!(tokenize("-d ${TCOBALT_TEST_DIR}/x").unwrap(), vec!["-d", "/tmp/my videos/x"]);
+    assert_eq!(tokenize("-d \"${TCOBALT_TEST_DIR}\" '${TCOBALT_TEST_DIR}' \\${X} $5").unwrap(), vec!["-d", "/tmp/my videos", "${TCOBALT_TEST_DIR}", "${X}", "$5"]);
+    assert_eq!(tokenize("a ${TCOBALT_TEST_UNSET}").unwrap_err(), (3, String::from("The environment variable TCOBALT_TEST_UNSET is not set")));
+    assert_eq!(tokenize("${bad name}").unwrap_err().0, 1);
+
+    let lines = parse_file("@output-dir \"${TCOBALT_TEST_DIR}\"\nhttps://youtu.be/a\n@default -a -q 720\nhttps://youtu.be/b -m\n@reset\nhttps://youtu.be/c\n").unwrap();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0].defaults, vec!["--output-dir", "/tmp/my videos"]);
+    assert_eq!(lines[1].defaults, vec!["--output-dir", "/tmp/my videos", "-a", "-q", "720"]);
+    assert_eq!(lines[1].standalone(), "--output-dir '/tmp/my videos' -a -q 720 https://youtu.be/b -m");
+    assert!(lines[2].defaults.is_empty());
+    assert!(parse_file("@nope x\n").unwrap_err().print().contains("On line 1, column 1 | Unknown directive @nope"));
+    assert!(parse_file("https://youtu.be/a\n@instance\n").unwrap_err().print().contains("On line 2"));
+    assert!(parse_file("@default -q 721\n").unwrap_err().print().contains("On line 1"));
+
+    let file = TempFile::new("bulk_directive", "@default -g -q 480\n@instance https://co.example.com/\nhttps://x.com/a/status/1\nhttps://youtu.be/zn5sTDXSp8E -q 1440\n");
+    let bulk = test_args(&["bulk", "execute", file.path()]).parse().unwrap();
+    let array = bulk.bulk_array.unwrap();
+    assert!(array.iter().all(|a| a.c_twitter_gif && a.cobalt_instance == "co.example.com"));
+    assert_eq!(array[0].c_video_quality, 480);
+    assert_eq!(array[1].c_video_quality, 1440);
+    // -g comes from a directive, so it isn't warned about for the youtube link
+    assert!(array[1].service_flags.is_empty());
+    assert_eq!(bulk.bulk_lines[1], "-g -q 480 --instance https://co.example.com/ https://youtu.be/zn5sTDXSp8E -q 1440");
+}
+
+#[test]
+fn bulk_url_lists() {
+    use crate::args::*;
+    use crate::args::bulk::parse_url_list;
+
+    let urls = parse_url_list("# links\nhttps://youtu.be/a\n\n  https://x.com/a/status/1   # a tweet\n").unwrap();
+    assert_eq!(urls, vec!["https://youtu.be/a", "https://x.com/a/status/1"]);
+    assert!(parse_url_list("https://youtu.be/a\nyoutu.be/b\n").unwrap_err().print().contains("On line 2, column 1"));
+    assert!(parse_url_list("https://youtu.be/a -a\n").is_err());
+
+    let file = TempFile::new("url_list", "https://youtu.be/zn5sTDXSp8E\nhttps://youtu.be/OnrbdAAokS0\n");
+    let path_str = file.path();
+    let bulk = test_args(&["bulk", "get", "--from-file", path_str, "-a"]).parse().unwrap();
+    let mixed = test_args(&["bulk", "get", "https://x.com/a/status/1", "--from-file", path_str]).parse().unwrap();
+    let array = bulk.bulk_array.unwrap();
+    assert_eq!(array.len(), 2);
+    assert!(array.iter().all(|a| a.c_download_mode == types::DownloadMode::Audio));
+    assert_eq!(bulk.bulk_lines[0], "https://youtu.be/zn5sTDXSp8E -a");
+    // links from the command line come first, then the ones from the file, each in the order they were given
+    let urls: Vec<String> = mixed.bulk_array.unwrap().into_iter().map(|a| a.c_url.unwrap()).collect();
+    assert_eq!(urls, vec!["https://x.com/a/status/1", "https://youtu.be/zn5sTDXSp8E", "https://youtu.be/OnrbdAAokS0"]);
+    assert!(test_args(&["bulk", "get", "--from-file"]).parse().is_err());
+    assert!(test_args(&["bulk", "get", "-a"]).parse().is_err());
+}
+
+#[test]
+fn completions() {
+    use crate::args::*;
+    use crate::args::spec::{self, Value};
+
+    assert_eq!(test_args(&["completions", "ZSH"]).parse().unwrap().method, Some(types::Method::Completions(String::from("zsh"))));
+    assert!(test_args(&["completions", "pwsh"]).parse().is_err());
+    assert!(test_args(&["completions"]).parse().is_err());
+
+    // every option in the table has to be one the parser knows
+    for flag in spec::GET_FLAGS.iter().chain(spec::SERVICE_FLAGS) {
+        let mut args = vec!["get", "https://youtu.be/zn5sTDXSp8E", flag.long];
+        let long = format!("--{}", flag.long);
+        args[2] = &long;
+        let value = match flag.value {
+            Value::Switch => None,
+            Value::Choice(choices) => Some(choices[0]),
+            Value::Instance => Some("https://co.example.com/"),
+            Value::Dir => Some("."),
+            Value::File => Some("out.mp4"),
+            Value::Text => Some("en")
+        };
+        args.extend(value);
+        if flag.long == "pick" {
+            args[3] = "1";
+        }
+        // profiles only exist in a config file
+        if flag.long == "profile" {
+            continue;
+        }
+        assert!(test_args(&args).parse().is_ok(), "{long} isn't accepted");
+    }
+
+    let bash = crate::completions::bash();
+    assert!(bash.contains("--vquality|-q) COMPREPLY=($(compgen -W \"144 480 720 1080 1440 2160\""));
+    assert!(bash.contains("--instance|-i) COMPREPLY=($(compgen -W \"$(_tcb_instances)\""));
+    assert!(crate::completions::zsh().contains("'(-o --output)'{-o,--output}'[The output filename, a template for it, or - for stdout]:file:_files'"));
+    assert!(crate::completions::fish().contains("-l retry-file -r -F"));
+}
+
+#[test]
+fn man_page() {
+    use crate::args::*;
+
+    assert_eq!(test_args(&["man"]).parse().unwrap().method, Some(types::Method::Man));
+    let page = crate::manpage::render("v1.0.0");
+    assert!(page.starts_with(".TH TCB 1 \"\" \"tcobalt v1.0.0\""));
+    for method in spec::METHODS {
+        assert!(page.contains(&format!(".SS {}", method.name.replace('-', "\\-"))), "{} is missing", method.name);
+    }
+    for flag in spec::GET_FLAGS.iter().chain(spec::SERVICE_FLAGS).chain(spec::BULK_GET_FLAGS) {
+        assert!(page.contains(&format!("\\-\\-{}", flag.long.replace('-', "\\-"))), "--{} is missing", flag.long);
+    }
+    assert!(page.contains(".TP\n\\fB\\-q \\-\\-vquality <quality>\\fR\nThe quality of the output video."));
+    assert!(page.contains("if\\-exists = rename\n"));
+    assert!(page.contains("\\fBTCOBALT_DEBUG\\fR"));
+    assert!(page.contains(".SH EXAMPLES\n.nf\ntcb get https://www.youtube.com/watch?v=zn5sTDXSp8E \\-m\n"));
+    // text that happens to start with a dot or quote can't be read as a request
+    let requests = [".TH", ".SH", ".SS", ".PP", ".TP", ".B ", ".br", ".nf", ".fi", ".RS", ".RE"];
+    assert!(page.lines().filter(|l| l.starts_with('.') || l.starts_with('\'')).all(|l| requests.iter().any(|r| l.starts_with(r))));
+}
+
+#[test]
+fn option_table() {
+    use crate::args::*;
+    use crate::args::spec::{self, Value};
+
+    // no two options a method takes can share a name
+    for method in spec::METHODS.iter().chain(spec::BULK_ACTIONS) {
+        let flags: Vec<&spec::Flag> = method.all_flags().collect();
+        for (i, flag) in flags.iter().enumerate() {
+            assert!(flags[i + 1..].iter().all(|f| f.long != flag.long && (f.short.is_none() || f.short != flag.short)), "--{} is taken twice in {}", flag.long, method.name);
+        }
+    }
+
+    let fname = spec::GET_FLAGS.iter().find(|f| f.long == "fname-style").unwrap();
+    assert_eq!(fname.check("P").unwrap(), "pretty");
+    assert_eq!(fname.check("nerdy").unwrap(), "nerdy");
+    assert!(fname.check("x").is_err());
+    let aformat = spec::GET_FLAGS.iter().find(|f| f.long == "aformat").unwrap();
+    assert!(aformat.check("o").is_err());
+    assert_eq!(test_args(&["get", "https://youtu.be/a", "--if-exists", "s"]).parse().unwrap().if_exists, types::Collision::Skip);
+    assert!(test_args(&["get", "https://youtu.be/a", "-b", "3"]).parse().is_err());
+    assert!(test_args(&["get", "https://youtu.be/a", "-q"]).parse().unwrap_err().print().contains("--vquality"));
+
+    // the generated config sets every option to what it already is without a config file
+    // which only holds while "plain" below is parsed without one, whatever config the person running the tests has
+    no_config();
+    assert!(!config::config_path().exists(), "the tests read {} instead of the built-in defaults", config::config_path().to_string_lossy());
+    let mut defaults: Vec<String> = Vec::new();
+    for line in spec::default_config().lines().skip(1) {
+        let (key, value) = line.split_once(" = ").unwrap();
+        for flag in spec::config_flags() {
+            defaults.append(&mut flag.config_args(key, value));
+        }
+    }
+    assert!(defaults.contains(&String::from("--auto")) && !defaults.contains(&String::from("--audio-only")));
+    let mut args = vec!["get", "https://youtu.be/a"];
+    args.extend(defaults.iter().map(|d| d.as_str()));
+    let mut configured = test_args(&args).parse().unwrap();
+    let plain = test_args(&["get", "https://youtu.be/a"]).parse().unwrap();
+    // given on the command line here, so --vcodec gets noted for the service warnings
+    configured.raw = plain.raw.clone();
+    configured.service_flags.clear();
+    assert_eq!(configured, plain);
+    assert!(spec::config_flags().filter(|f| f.value != Value::Switch && f.config != spec::Config::None).all(|f| spec::default_config().contains(&format!("\n{} = ", f.long))));
+    // each default in the table is the value tcobalt sends without the option, and only options that are off by default have none
+    for flag in spec::config_flags().filter(|f| f.value != Value::Switch) {
+        if let Some(default) = flag.default.filter(|_| matches!(flag.config, spec::Config::Key(_))) {
+            let mut applied = crate::args::Args::get();
+            (flag.apply)(&mut applied, default).unwrap();
+            assert_eq!(applied, crate::args::Args::get(), "--{} {default}", flag.long);
+        }
+    }
+    let without: Vec<&str> = spec::config_flags().filter(|f| f.value != Value::Switch && f.default.is_none() && f.config != spec::Config::None).map(|f| f.long).collect();
+    assert_eq!(without, vec!["output-dir", "archive", "exec"]);
+
+    let help = spec::usage("get");
+    assert!(!help.contains("{options"));
+    assert!(help.contains("\t-q --vquality <quality>    The quality of the output video. Options: 144, 480, 720, 1080, 1440, 2160, Default: 1080\n"));
+    assert!(spec::usage("config").contains("download-mode = audio/mute/auto"));
+}
+
+#[test]
+fn gnu_style_options() {
+    use crate::args::*;
+    let url = "https://www.youtube.com/watch?v=zn5sTDXSp8E";
+
+    let attached = test_args(&["get", url, "--vquality=720", "-fogg", "-s", "p", "-oOut.ogg", "--exec=echo {path}"]).parse().unwrap();
+    assert_eq!(attached.c_video_quality, 720);
+    assert_eq!(attached.c_audio_format, types::AudioFormat::OGG);
+    assert_eq!(attached.c_fname_style, types::FilenamePattern::Pretty);
+    assert_eq!(attached.out_filename, Some(String::from("Out.ogg")));
+    assert_eq!(attached.exec, Some(String::from("echo {path}")));
+    // the rest of a group is only a value when it isn't made of other short options
+    let grouped = test_args(&["get", url, "-cq", "vp9", "720", "-q1440", "-p2"]).parse().unwrap();
+    assert_eq!(grouped.c_video_codec, types::VideoCodec::VP9);
+    assert_eq!(grouped.c_video_quality, 1440);
+    assert_eq!(grouped.picker_choice, 2);
+
+    let prefixed = test_args(&["get", url, "--vq", "480", "--if=skip", "--no-meta"]).parse().unwrap();
+    assert_eq!(prefixed.c_video_quality, 480);
+    assert_eq!(prefixed.if_exists, types::Collision::Skip);
+    assert!(prefixed.c_disable_metadata);
+    assert!(test_args(&["get", url, "--out", "a.mp4"]).parse().unwrap_err().print().contains("Ambiguous argument (--output, --output-dir)"));
+    assert!(test_args(&["get", url, "--audio-only=true"]).parse().is_err());
+
+    let ended = test_args(&["get", "-a", "--", url]).parse().unwrap();
+    assert_eq!(ended.c_url, Some(url.to_string()));
+    assert!(test_args(&["get", url, "--", "-a"]).parse().is_err());
+    let dashed = test_args(&["get", url, "-o", "-dash.mp4"]).parse().unwrap();
+    assert_eq!(dashed.out_filename, Some(String::from("-dash.mp4")));
+
+    let bulk = test_args(&["bulk", "get", "--aud", "--retry-file=failed.txt", "--", url]).parse().unwrap();
+    assert_eq!(bulk.retry_file, Some(String::from("failed.txt")));
+    assert_eq!(bulk.bulk_array.unwrap()[0].c_download_mode, types::DownloadMode::Audio);
+    assert_eq!(bulk.bulk_lines[0], format!("{url} --aud --"));
+
+    let file = TempFile::new("gnu", &format!("{url} -q720\n"));
+    let execute = test_args(&["bulk", "execute", "--dry", "--", file.path()]).parse();
+    let execute = execute.unwrap();
+    assert!(execute.dry_run);
+    assert_eq!(execute.bulk_array.unwrap()[0].c_video_quality, 720);
+}
+
+#[test]
+fn config_profiles() {
+    use crate::args::*;
+    let text = "[default]\nvquality = 720\nno-metadata = true\ndownload-mode = mute\n\n[default.instances]\ncobalt.example.com\n\n[profile.music]\ndownload-mode = audio\nno-metadata = false\n\n[profile.archive]\n# comments are fine\ninstance = 1\n";
+    assert_eq!(config::profiles(text), vec![String::from("music"), String::from("archive")]);
+    let pairs = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<Vec<(String, String)>>();
+
+    assert_eq!(config::options(text, None).unwrap(), pairs(&[("vquality", "720"), ("no-metadata", "true"), ("download-mode", "mute")]));
+    // keys the profile sets replace the ones from [default], even to turn a switch back off
+    assert_eq!(config::options(text, Some("music")).unwrap(), pairs(&[("vquality", "720"), ("download-mode", "audio"), ("no-metadata", "false")]));
+    assert_eq!(config::options(text, Some("archive")).unwrap(), pairs(&[("vquality", "720"), ("no-metadata", "true"), ("download-mode", "mute"), ("instance", "1")]));
+    assert_eq!(config::options(text, Some("video")).unwrap_err(), "The profile \"video\" doesn't exist (profiles in the config file: music, archive)");
+    assert!(config::options("[default]\n", Some("music")).unwrap_err().contains("no [profile.NAME] sections"));
+
+    assert!(test_args(&["get", "https://www.youtube.com/watch?v=zn5sTDXSp8E", "--profile", "tcobalt-test-missing"]).parse().is_err());
+    assert!(spec::usage("get").contains("--profile <name>"));
+    assert!(spec::usage("environment").contains("TCOBALT_PROFILE"));
+}
+
+#[test]
+fn config_check() {
+    use crate::args::*;
+    let generated = format!("{}\n{}", spec::default_config(), crate::strings::get_str("info", "default-instances").replace('\\', ""));
+    assert_eq!(config::check(&generated), Vec::new());
+    assert_eq!(config::check("[default]\nvquality = 720\ninstance = 1\n\n[default.instances]\nco.example.com\n\n[profile.music]\n# audio only\ndownload-mode = AUDIO\n"), Vec::new());
+
+    let text = "stray = 1\n[default]\nvquality = 721\nvquality = 720\nno-metadata = yes\ndownload-mode = video\nbogus = 1\nnot a pair\ninstance = 5\nexec = echo {nope}\noutput-dir = none\n\n[defaults]\nx = y\n\n[default.instances]\nco.example.com\n[profile.music]\naformat = o\n[default]\n";
+    let problems = config::check(text);
+    let lines: Vec<usize> = problems.iter().map(|p| p.line).collect();
+    assert_eq!(lines, vec![1, 3, 4, 5, 6, 7, 8, 9, 10, 13, 19, 20]);
+    assert_eq!(problems[1].reason, "Invalid value for vquality: 721 (144/480/720/1080/1440/2160)");
+    assert_eq!(problems[2].reason, "The key vquality is already set on line 3");
+    assert_eq!(problems[4].reason, "Invalid value for download-mode: video (audio/mute/auto)");
+    assert!(problems[5].reason.starts_with("Unknown key bogus"));
+    assert_eq!(problems[8].reason.lines().count(), 2);
+    // only invalid values in the sections that get loaded stop tcobalt, the rest is left to `tcb config check`
+    let blocking = |profile| problems.iter().filter(|p| p.blocks(profile)).map(|p| p.line).collect::<Vec<usize>>();
+    assert_eq!(blocking(None), vec![3, 5, 6, 9, 10]);
+    assert_eq!(blocking(Some("music")), vec![3, 5, 6, 9, 10, 19]);
+
+    let report = config::diagnostics(std::path::Path::new("/home/me/.config/tcobalt.conf"), &problems);
+    assert_eq!(report.lines().count(), problems.len());
+    assert!(report.starts_with("/home/me/.config/tcobalt.conf:1: This line isn't inside a section"));
+    assert!(report.contains("/home/me/.config/tcobalt.conf:20: The section [default] is already on line 2"));
+
+    assert_eq!(types::ParseError::throw_config("").title(), "Invalid config file");
+    assert_eq!(types::ParseError::throw_invalid("").title(), "Invalid command syntax");
+    assert_eq!(test_args(&["config", "check"]).parse().unwrap().method, Some(types::Method::Config(types::ConfigAction::Check)));
+    assert!(test_args(&["config", "fix"]).parse().is_err());
+}
+
+#[test]
+fn config_editing() {
+    use crate::args::*;
+    // the keys complete from the options themselves, so every key in the defaults shows up
+    assert!(crate::completions::fish().contains(&spec::config_defaults().iter().map(|(key, _)| *key).collect::<Vec<&str>>().join(" ")));
+
+    let text = "# my config\n[default]\nvquality = 720\n# keep metadata\nno-metadata = false\n\n[default.instances]\n# main ones\nco.example.com\n\n# backup\nhttps://two.example.com/\n\n[profile.music]\ndownload-mode = audio\n";
+    let set = config::set_key(text, "default", "vquality", "1440");
+    assert_eq!(set, text.replace("vquality = 720", "vquality = 1440"));
+    let added = config::set_key(text, "default", "aformat", "opus");
+    assert_eq!(added, text.replace("no-metadata = false\n", "no-metadata = false\naformat = opus\n"));
+    let profile = config::set_key(text, "profile.video", "proxy", "true");
+    assert_eq!(profile, format!("{text}\n[profile.video]\nproxy = true\n"));
+    assert_eq!(config::set_key("", "default", "vcodec", "av1"), "[default]\nvcodec = av1\n");
+    assert_eq!(config::set_key("[default]\nvcodec = vp9\nVCODEC = av1\n", "default", "vcodec", "h264"), "[default]\nvcodec = h264\n");
+
+    assert_eq!(config::unset_key(text, "default", "no-metadata").unwrap(), text.replace("no-metadata = false\n", ""));
+    assert_eq!(config::unset_key(text, "default", "aformat"), None);
+    assert_eq!(config::unset_key(text, "profile.video", "proxy"), None);
+
+    assert_eq!(config::lookup(text, None, "vquality").unwrap(), (String::from("720"), config::Source::File(String::from("default"), 3, String::from("720"))));
+    assert_eq!(config::lookup(text, Some("music"), "download-mode").unwrap(), (String::from("audio"), config::Source::File(String::from("profile.music"), 15, String::from("audio"))));
+    assert_eq!(config::lookup(text, Some("music"), "no-metadata").unwrap().1, config::Source::File(String::from("default"), 5, String::from("false")));
+    // values show up the way the options read them, and as they are written only in the source
+    let written = format!("{text}\n[profile.short]\nfname-style = p\ninstance = 2\ndownload-mode = Mute\nno-metadata = TRUE\n");
+    assert_eq!(config::lookup(&written, Some("short"), "fname-style").unwrap(), (String::from("pretty"), config::Source::File(String::from("profile.short"), 18, String::from("p"))));
+    assert_eq!(config::lookup(&written, Some("short"), "instance").unwrap().0, "two.example.com");
+    assert_eq!(config::lookup(&written, Some("short"), "download-mode").unwrap().0, "mute");
+    assert_eq!(config::lookup(&written, Some("short"), "no-metadata").unwrap().0, "true");
+    assert_eq!(config::lookup(text, None, "download-mode").unwrap(), (String::from("auto"), config::Source::BuiltIn));
+    assert!(config::lookup(text, Some("video"), "vquality").is_err());
+    assert!(config::lookup(text, None, "bogus").is_err());
+    // the built-in value shown for every key is the one tcobalt sends without a config file
+    let plain = test_args(&["get", "https://youtu.be/a"]).parse().unwrap();
+    for (key, _) in spec::config_defaults() {
+        let (value, source) = config::lookup("", None, key).unwrap();
+        assert_eq!(source, config::Source::BuiltIn);
+        // "none" only for the options that really are unset without a config file, since nothing would be checked for it below
+        assert_eq!(value == "none", ["output-dir", "archive", "exec"].contains(&key), "{key}");
+        let mut args = vec![String::from("get"), String::from("https://youtu.be/a")];
+        args.extend(spec::config_flags().flat_map(|flag| flag.config_args(key, &value)));
+        let mut looked_up = test_args(&args.iter().map(|a| a.as_str()).collect::<Vec<&str>>()).parse().unwrap();
+        looked_up.raw = plain.raw.clone();
+        looked_up.service_flags.clear();
+        assert_eq!(looked_up, plain, "{key} = {value}");
+    }
+
+    assert_eq!(config::instance_entries(text), vec![String::from("co.example.com"), String::from("https://two.example.com/")]);
+    let instances = [String::from("two.example.com"), String::from("co.example.com"), String::from("three.example.com")];
+    let replaced = config::replace_instances(text, &instances);
+    assert!(replaced.contains("[default.instances]\n# main ones\ntwo.example.com\n\n# backup\nco.example.com\nthree.example.com\n\n[profile.music]"));
+    assert!(config::replace_instances(text, &[]).contains("[default.instances]\n# main ones\n\n# backup\n\n[profile.music]"));
+    assert_eq!(config::replace_instances("[default]\n", &instances[..1]), "[default]\n\n[default.instances]\ntwo.example.com\n");
+    // what --reorder writes when there is no config file yet
+    assert_eq!(config::check(&config::replace_instances(&spec::default_config(), &instances)), Vec::new());
+
+    let parse = |args: &[&str]| test_args(args).parse().map(|args| (args.method.unwrap(), args.profile));
+    assert_eq!(parse(&["config", "set", "VQuality", "720", "--profile", "music"]).unwrap(), (types::Method::Config(types::ConfigAction::Set(String::from("vquality"), String::from("720"))), Some(String::from("music"))));
+    assert_eq!(parse(&["config", "move-instance", "two.example.com", "1"]).unwrap().0, types::Method::Config(types::ConfigAction::MoveInstance(String::from("two.example.com"), 1)));
+    assert_eq!(parse(&["config", "add-instance", "co.example.com"]).unwrap().0, types::Method::Config(types::ConfigAction::AddInstance(String::from("co.example.com"), None)));
+    assert!(parse(&["config", "move-instance", "1", "0"]).unwrap_err().print().contains("Invalid position: 0"));
+    assert!(parse(&["config", "get"]).unwrap_err().print().contains("Missing arguments for config get"));
+    assert!(parse(&["config", "get", "vquality", "aformat"]).unwrap_err().print().contains("Unrecognized argument: aformat"));
+    assert!(parse(&["config", "check", "--profile", "music"]).is_err());
+}